@@ -0,0 +1,67 @@
+//! Run once with `cargo bench` and once with `cargo bench --features simd`
+//! to compare the scalar and SIMD `Vec3` paths. With the feature enabled,
+//! the packet benchmarks also compare 4-wide intersection against four
+//! scalar calls within the same run.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use raytracer::hittable::Hittable;
+use raytracer::sphere::Sphere;
+use raytracer::float::Float;
+use raytracer::{Aabb, Material, Ray, Vec3};
+
+fn rays() -> [Ray; 4] {
+    let origin = Vec3::new(0.0, 0.0, 5.0);
+    [
+        Ray::new(origin, Vec3::new(0.01, 0.0, -1.0)),
+        Ray::new(origin, Vec3::new(-0.02, 0.01, -1.0)),
+        Ray::new(origin, Vec3::new(0.5, 0.5, -1.0)),
+        Ray::new(origin, Vec3::new(0.0, -0.03, -1.0)),
+    ]
+}
+
+fn vec3_math(c: &mut Criterion) {
+    let a = Vec3::new(0.3, -1.2, 2.5);
+    let b = Vec3::new(1.7, 0.4, -0.9);
+    c.bench_function("vec3 arithmetic", |bench| {
+        bench.iter(|| {
+            let (a, b) = (black_box(a), black_box(b));
+            let v = (a + b) * (a - b) * 0.5 + Vec3::cross(&a, &b);
+            Vec3::dot(&v, &a) + v.squared_length()
+        })
+    });
+}
+
+fn scalar_intersection(c: &mut Criterion) {
+    let sphere = Sphere::new(Vec3::default(), 1.0, Material::default());
+    let aabb = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+    let rays = rays();
+    c.bench_function("sphere x4 scalar", |bench| {
+        bench.iter(|| black_box(rays).map(|r| sphere.hit(&r, 0.001, Float::MAX)))
+    });
+    c.bench_function("aabb x4 scalar", |bench| {
+        bench.iter(|| black_box(rays).map(|r| aabb.hit(&r, 0.001, Float::MAX)))
+    });
+}
+
+#[cfg(feature = "simd")]
+fn packet_intersection(c: &mut Criterion) {
+    use raytracer::packet::RayPacket4;
+
+    let sphere = Sphere::new(Vec3::default(), 1.0, Material::default());
+    let aabb = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+    let packet = RayPacket4::new(&rays());
+    c.bench_function("sphere packet4", |bench| {
+        bench.iter(|| sphere.hit_packet(black_box(&packet), 0.001, Float::MAX))
+    });
+    c.bench_function("aabb packet4", |bench| {
+        bench.iter(|| aabb.hit_packet(black_box(&packet), 0.001, Float::MAX))
+    });
+}
+
+#[cfg(not(feature = "simd"))]
+fn packet_intersection(_c: &mut Criterion) {}
+
+criterion_group!(benches, vec3_math, scalar_intersection, packet_intersection);
+criterion_main!(benches);
@@ -0,0 +1,163 @@
+use crate::float::Float;
+use crate::vec3::Vec3;
+use crate::ray::Ray;
+#[cfg(feature = "simd")]
+use crate::packet::RayPacket4;
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3 ) -> Aabb {
+        Aabb {
+            min,
+            max,
+        }
+    }
+
+    /// Smallest box containing all `points`, grown to at least `delta` along
+    /// every axis so flat shapes still have a box the slab test can hit.
+    pub fn from_points(points: &[Vec3], delta: Float) -> Aabb {
+        let mut min = Vec3::new(Float::MAX, Float::MAX, Float::MAX);
+        let mut max = -min;
+        for p in points {
+            for i in 0..3 {
+                min.e[i] = min.e[i].min(p.e[i]);
+                max.e[i] = max.e[i].max(p.e[i]);
+            }
+        }
+        for i in 0..3 {
+            if max.e[i] - min.e[i] < delta {
+                min.e[i] -= delta / 2.0;
+                max.e[i] += delta / 2.0;
+            }
+        }
+        Aabb::new(min, max)
+    }
+
+    pub fn min(&self) -> Vec3 {
+        self.min
+    }
+
+    pub fn max(&self) -> Vec3 {
+        self.max
+    }
+
+    /// Smallest box containing both boxes.
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Aabb {
+        Aabb::new(
+            Vec3::new(a.min.x().min(b.min.x()), a.min.y().min(b.min.y()), a.min.z().min(b.min.z())),
+            Vec3::new(a.max.x().max(b.max.x()), a.max.y().max(b.max.y()), a.max.z().max(b.max.z())),
+        )
+    }
+
+    /// The eight corners of the box.
+    pub fn corners(&self) -> [Vec3; 8] {
+        let mut corners = [Vec3::default(); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            *corner = Vec3::new(
+                if i & 1 == 0 { self.min.x() } else { self.max.x() },
+                if i & 2 == 0 { self.min.y() } else { self.max.y() },
+                if i & 4 == 0 { self.min.z() } else { self.max.z() },
+            );
+        }
+        corners
+    }
+
+    /// Slab test for four rays at once. Returns a bitmask with bit `i` set
+    /// if the ray in lane `i` overlaps the box inside `[t_min, t_max]`.
+    #[cfg(feature = "simd")]
+    pub fn hit_packet(&self, packet: &RayPacket4, t_min: Float, t_max: Float) -> u32 {
+        use wide::CmpGt;
+        use crate::float::FloatX4;
+
+        let mut t_min = FloatX4::splat(t_min);
+        let mut t_max = FloatX4::splat(t_max);
+        for a in 0..3 {
+            let t0 = (FloatX4::splat(self.min.e[a]) - packet.origin[a]) * packet.inv_direction[a];
+            let t1 = (FloatX4::splat(self.max.e[a]) - packet.origin[a]) * packet.inv_direction[a];
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+        }
+        t_max.simd_gt(t_min).to_bitmask()
+    }
+
+    pub fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.clip(ray, t_min, t_max).is_some()
+    }
+
+    /// Part of `[t_min, t_max]` in which the ray is inside the box.
+    pub fn clip(&self, ray: &Ray, mut t_min: Float, mut t_max: Float) -> Option<(Float, Float)> {
+        for a in 0..3 {
+            let t0 = min_max::min((self.min.e[a] - ray.origin().e[a]) / ray.direction().e[a], (self.max.e[a] - ray.origin().e[a]) / ray.direction().e[a]);
+            let t1 = min_max::max((self.min.e[a] - ray.origin().e[a]) / ray.direction().e[a], (self.max.e[a] - ray.origin().e[a]) / ray.direction().e[a]);
+            t_min = min_max::max(t0, t_min);
+            t_max = min_max::min(t1, t_max);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
+
+#[cfg(all(test, feature = "simd"))]
+mod tests {
+    use super::*;
+    use crate::float::Float;
+    use crate::packet::RayPacket4;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const CASES: usize = 1000;
+
+    fn random_vec3(rng: &mut StdRng, range: Float) -> Vec3 {
+        Vec3::new(rng.gen_range(-range..range), rng.gen_range(-range..range), rng.gen_range(-range..range))
+    }
+
+    fn assert_lanes_match(aabb: &Aabb, rays: &[Ray; 4], t_min: Float, t_max: Float) -> u32 {
+        let mask = aabb.hit_packet(&RayPacket4::new(rays), t_min, t_max);
+        for (lane, ray) in rays.iter().enumerate() {
+            assert_eq!(mask & (1 << lane) != 0, aabb.hit(ray, t_min, t_max), "lane {} {:?} in [{}, {}]", lane, ray, t_min, t_max);
+        }
+        mask
+    }
+
+    #[test]
+    fn packet_lanes_match_scalar_hits() {
+        let mut rng = StdRng::seed_from_u64(34);
+        let aabb = Aabb::new(Vec3::new(-1.0, -0.5, -2.0), Vec3::new(1.5, 0.5, 1.0));
+        let (mut hits, mut misses) = (0, 0);
+        for _ in 0..CASES {
+            let rays: [Ray; 4] = std::array::from_fn(|_| {
+                let origin = random_vec3(&mut rng, 4.0);
+                Ray::new(origin, random_vec3(&mut rng, 2.0) - origin)
+            });
+            let (t_min, t_max) = (rng.gen_range(0.0..0.5), rng.gen_range(0.5..3.0));
+            let mask = assert_lanes_match(&aabb, &rays, t_min, t_max);
+            hits += mask.count_ones();
+            misses += 4 - mask.count_ones();
+        }
+        assert!(hits > 100 && misses > 100, "{} hits, {} misses", hits, misses);
+    }
+
+    #[test]
+    fn packet_handles_axis_aligned_rays() {
+        let aabb = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+        let rays = [
+            Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)),
+            Ray::new(Vec3::new(2.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)),
+            Ray::new(Vec3::new(-5.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0)),
+            Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+        ];
+        assert_eq!(assert_lanes_match(&aabb, &rays, 0.0, Float::MAX), 0b1101);
+        // The ray starting inside leaves at t = 1, the others cross from 4 to 6
+        assert_eq!(assert_lanes_match(&aabb, &rays, 5.0, 10.0), 0b0101);
+        assert_eq!(assert_lanes_match(&aabb, &rays, 6.5, 10.0), 0b0000);
+        assert_eq!(assert_lanes_match(&aabb, &rays, 0.5, 10.0), 0b1101);
+    }
+}
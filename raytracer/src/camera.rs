@@ -1,12 +1,11 @@
+use crate::float::Float;
 use crate::vec3::Vec3;
 use crate::ray::Ray;
-use crate::random_in_unit_sphere;
+use crate::sampler::sample_unit_disk;
 
 extern crate rand;
 
-use std::f64::consts::PI;
-
-use rand::Rng;
+use crate::float::consts::PI;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Camera {
@@ -16,7 +15,7 @@ pub struct Camera {
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    lens_radius: f64,
+    lens_radius: Float,
 }
 
 impl Camera {
@@ -24,10 +23,10 @@ impl Camera {
         look_from: Vec3,
         look_at: Vec3,
         v_up: Vec3,
-        v_fov: f64,
-        aspect_ratio: f64,
+        v_fov: Float,
+        aspect_ratio: Float,
     ) -> Self {
-        let theta = v_fov * std::f64::consts::PI / 180.0;
+        let theta = v_fov * PI / 180.0;
         let half_height = (theta / 2.0).tan();
         let half_width = aspect_ratio * half_height;
         let w = Vec3::unit_vector(&(look_from - look_at));
@@ -50,10 +49,11 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * random_in_unit_sphere();
+    /// `lens` is a uniform 2D sample used to pick the point on the lens.
+    pub fn get_ray(&self, s: Float, t: Float, lens: (Float, Float)) -> Ray {
+        let rd = self.lens_radius * sample_unit_disk(lens);
         let offset = self.u * rd.x() + self.v * rd.y();
-        Ray::ray(
+        Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
         )
@@ -65,19 +65,19 @@ impl Camera {
 //         let mut rng = rand::thread_rng();
 //         let mut p: Vec3;
 //         while {
-//             p = 2.0*Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), 0.0) - Vec3::new(1.0, 1.0, 0.0);
+//             p = 2.0*Vec3::new(rng.gen::<Float>(), rng.gen::<Float>(), 0.0) - Vec3::new(1.0, 1.0, 0.0);
 //             Vec3::dot(&p, &p) >= 1.0
 //         } {}
 //         p
 //     }
 
-//     pub fn new(lookfrom: Vec3, lookat: Vec3, vup: Vec3, vfov: f64, 
-//         aspect: f64, aperture: f64, focus_dist: f64) -> Camera {
-//             let lens_radius: f64 = aperture/2.0;
+//     pub fn new(lookfrom: Vec3, lookat: Vec3, vup: Vec3, vfov: Float, 
+//         aspect: Float, aperture: Float, focus_dist: Float) -> Camera {
+//             let lens_radius: Float = aperture/2.0;
     
-//             let theta: f64 = vfov*PI/180.0;
-//             let half_height: f64 = (theta/2.0).tan();
-//             let half_width: f64 = aspect * half_height;
+//             let theta: Float = vfov*PI/180.0;
+//             let half_height: Float = (theta/2.0).tan();
+//             let half_width: Float = aspect * half_height;
     
 //             let origin: Vec3 = lookfrom;
             
@@ -100,10 +100,10 @@ impl Camera {
 //                 }
 //     }
 
-//     pub fn get_ray(&self, u: f64, v: f64) -> Ray {
+//     pub fn get_ray(&self, u: Float, v: Float) -> Ray {
 //         let rd: Vec3 = self.lens_radius * random_in_unit_sphere();
 //         let offset: Vec3 = self.u * rd.x() + self.v * rd.y();
-//         Ray::ray(self.origin + offset,
+//         Ray::new(self.origin + offset,
 //                  self.lower_left_corner + u*self.horizontal + v*self.vertical - self.origin - offset)
 //     }
 // }
\ No newline at end of file
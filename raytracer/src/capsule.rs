@@ -0,0 +1,221 @@
+use crate::float::{Float, gamma};
+use crate::float::consts::PI;
+use crate::vec3::Vec3;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::roots::solve_quadratic;
+
+#[derive(Debug, Clone, Copy)]
+enum Part {
+    Side,
+    Bottom,
+    Top,
+}
+
+/// Cylinder of `height` with hemispherical ends, standing upright with the
+/// centre of its lower hemisphere at `center`. Wrap it in a `Transform` to
+/// point it along another axis.
+pub struct Capsule {
+    center: Vec3,
+    radius: Float,
+    height: Float,
+    material: Material,
+}
+
+impl Capsule {
+    pub fn new(center: Vec3, radius: Float, height: Float, material: Material) -> Capsule {
+        Capsule {
+            center,
+            radius,
+            height,
+            material,
+        }
+    }
+
+    /// Surface hits along the whole ray, in no particular order.
+    fn intersections(&self, r: &Ray) -> ([(Float, Part); 6], usize) {
+        let o = r.origin() - self.center;
+        let d = r.direction();
+        let mut hits = [(0.0, Part::Side); 6];
+        let mut n = 0;
+        let r2 = self.radius * self.radius;
+
+        let a = d.x() * d.x() + d.z() * d.z();
+        if a != 0.0 {
+            let b = 2.0 * (o.x() * d.x() + o.z() * d.z());
+            let c = o.x() * o.x() + o.z() * o.z() - r2;
+            if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+                for t in [t0, t1] {
+                    if (0.0..=self.height).contains(&(o.y() + t * d.y())) {
+                        hits[n] = (t, Part::Side);
+                        n += 1;
+                    }
+                }
+            }
+        }
+
+        // Each cap only counts on its own half of the sphere
+        for (y, part) in [(0.0, Part::Bottom), (self.height, Part::Top)] {
+            let oc = o - Vec3::new(0.0, y, 0.0);
+            let roots = solve_quadratic(d.squared_length(), 2.0 * Vec3::dot(&oc, &d), oc.squared_length() - r2);
+            if let Some((t0, t1)) = roots {
+                for t in [t0, t1] {
+                    let above = oc.y() + t * d.y();
+                    let outside = match part {
+                        Part::Bottom => above < 0.0,
+                        _ => above > 0.0,
+                    };
+                    if outside {
+                        hits[n] = (t, part);
+                        n += 1;
+                    }
+                }
+            }
+        }
+        (hits, n)
+    }
+
+    /// Hit record for `part` at `t`, with the point projected back onto it.
+    fn record(&self, r: &Ray, t: Float, part: Part) -> HitRecord {
+        let local = r.point_at_parameter(t) - self.center;
+        let axis = match part {
+            Part::Side => Vec3::new(0.0, local.y(), 0.0),
+            Part::Bottom => Vec3::default(),
+            Part::Top => Vec3::new(0.0, self.height, 0.0),
+        };
+        let outward_normal = Vec3::unit_vector(&(local - axis));
+        let local = axis + outward_normal * self.radius;
+        let p = self.center + local;
+
+        // v is linear in height, so on the caps the ring shrinks towards the poles
+        let span = self.height + 2.0 * self.radius;
+        let dpdv = match part {
+            Part::Side => Vec3::new(0.0, span, 0.0),
+            _ => {
+                let (rise, rho2) = (local.y() - axis.y(), local.x() * local.x() + local.z() * local.z());
+                if rho2 > 0.0 {
+                    Vec3::new(-local.x() * rise / rho2, 1.0, -local.z() * rise / rho2) * span
+                } else {
+                    Vec3::default()
+                }
+            }
+        };
+
+        let mut rec = HitRecord {
+            t,
+            p,
+            p_error: local.abs() * gamma(5) + p.abs() * gamma(1),
+            u: local.z().atan2(local.x()) / (2.0 * PI) + 0.5,
+            v: (local.y() + self.radius) / span,
+            dpdu: Vec3::new(-local.z(), 0.0, local.x()) * (2.0 * PI),
+            dpdv,
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(r, outward_normal);
+        rec
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (hits, n) = self.intersections(r);
+        hits[..n]
+            .iter()
+            .filter(|(t, _)| *t > t_min && *t < t_max)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|&(t, part)| self.record(r, t, part))
+    }
+
+    fn hit_all(&self, r: &Ray) -> Vec<HitRecord> {
+        let (hits, n) = self.intersections(r);
+        let mut hits = hits[..n].to_vec();
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        hits.into_iter().map(|(t, part)| self.record(r, t, part)).collect()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        Some(Aabb::new(
+            self.center - Vec3::new(r, r, r),
+            self.center + Vec3::new(r, self.height + r, r),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::{check_surface_hit, ray_through};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-9;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-5;
+
+    const CASES: usize = 1000;
+
+    fn capsule() -> Capsule {
+        Capsule::new(Vec3::default(), 1.0, 2.0, Material::default())
+    }
+
+    fn inside(capsule: &Capsule, p: Vec3) -> bool {
+        let local = p - capsule.center;
+        let axis = Vec3::new(0.0, local.y().clamp(0.0, capsule.height), 0.0);
+        (local - axis).length() <= capsule.radius
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_the_side_or_the_caps() {
+        let capsule = capsule();
+
+        let across = Ray::new(Vec3::new(5.0, 1.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let rec = capsule.hit(&across, 0.0, Float::MAX).unwrap();
+        assert!((rec.t - 4.0).abs() < TOLERANCE);
+        assert!((Vec3::from(rec.normal) - Vec3::new(1.0, 0.0, 0.0)).length() < TOLERANCE);
+        assert!((rec.v - 0.5).abs() < TOLERANCE);
+
+        let axis = Ray::new(Vec3::new(0.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hits = capsule.hit_all(&axis);
+        assert_eq!(hits.len(), 2);
+        assert!((hits[0].t - 7.0).abs() < TOLERANCE && hits[0].front_face && (hits[0].v - 1.0).abs() < TOLERANCE);
+        assert!((hits[1].t - 11.0).abs() < TOLERANCE && !hits[1].front_face && hits[1].v.abs() < TOLERANCE);
+
+        let cap = Ray::new(Vec3::new(0.6, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = capsule.hit(&cap, 0.0, Float::MAX).unwrap();
+        assert!((rec.t - 7.2).abs() < TOLERANCE);
+        assert!((Vec3::from(rec.normal) - Vec3::new(0.6, 0.8, 0.0)).length() < TOLERANCE);
+
+        let up = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = capsule.hit(&up, 0.0, Float::MAX).unwrap();
+        assert!((rec.t - 2.0).abs() < TOLERANCE);
+        assert!(!rec.front_face);
+
+        let outside = Ray::new(Vec3::new(1.1, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(capsule.hit(&outside, 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn random_hits_lie_on_the_surface() {
+        let mut rng = StdRng::seed_from_u64(38);
+        let capsule = capsule();
+        let aabb = capsule.bounding_box().unwrap();
+        let mut hits = 0;
+        for _ in 0..CASES {
+            let ray = ray_through(&mut rng, &aabb);
+            let all = capsule.hit_all(&ray);
+            assert!(all.windows(2).all(|pair| pair[0].t <= pair[1].t));
+            for rec in &all {
+                check_surface_hit(&capsule, &ray, rec, |p| inside(&capsule, p));
+            }
+            let nearest = all.iter().find(|rec| rec.t > 0.0).map(|rec| rec.t);
+            assert_eq!(capsule.hit(&ray, 0.0, Float::MAX).map(|rec| rec.t), nearest);
+            hits += all.len();
+        }
+        assert!(hits > CASES);
+    }
+}
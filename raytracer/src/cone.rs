@@ -0,0 +1,221 @@
+use crate::float::{Float, gamma};
+use crate::float::consts::PI;
+use crate::vec3::Vec3;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::roots::solve_quadratic;
+
+#[derive(Debug, Clone, Copy)]
+enum Part {
+    Side,
+    Base,
+}
+
+/// Closed cone standing on the centre of its base disk with the apex
+/// `height` above it. Wrap it in a `Transform` to point it along another axis.
+pub struct Cone {
+    center: Vec3,
+    radius: Float,
+    height: Float,
+    material: Material,
+}
+
+impl Cone {
+    pub fn new(center: Vec3, radius: Float, height: Float, material: Material) -> Cone {
+        Cone {
+            center,
+            radius,
+            height,
+            material,
+        }
+    }
+
+    /// Surface hits along the whole ray, in no particular order.
+    fn intersections(&self, r: &Ray) -> ([(Float, Part); 3], usize) {
+        let o = r.origin() - self.center;
+        let d = r.direction();
+        let mut hits = [(0.0, Part::Side); 3];
+        let mut n = 0;
+
+        // x^2 + z^2 = (k (h - y))^2 with k the slope of the side
+        let k2 = (self.radius / self.height) * (self.radius / self.height);
+        let h = self.height - o.y();
+        let a = d.x() * d.x() + d.z() * d.z() - k2 * d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z() + k2 * h * d.y());
+        let c = o.x() * o.x() + o.z() * o.z() - k2 * h * h;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            let roots = if t0 == t1 { &[t0][..] } else { &[t0, t1][..] };
+            for &t in roots {
+                // The equation also describes the mirrored cone above the apex
+                let y = o.y() + t * d.y();
+                if (0.0..=self.height).contains(&y) {
+                    hits[n] = (t, Part::Side);
+                    n += 1;
+                }
+            }
+        }
+
+        if d.y() != 0.0 {
+            let t = -o.y() / d.y();
+            let (x, z) = (o.x() + t * d.x(), o.z() + t * d.z());
+            if x * x + z * z <= self.radius * self.radius {
+                hits[n] = (t, Part::Base);
+                n += 1;
+            }
+        }
+        (hits, n)
+    }
+
+    /// Hit record for `part` at `t`, with the point projected back onto it.
+    fn record(&self, r: &Ray, t: Float, part: Part) -> HitRecord {
+        let mut local = r.point_at_parameter(t) - self.center;
+        let radial = Vec3::new(local.x(), 0.0, local.z()).length();
+        let outward_normal = match part {
+            Part::Side => {
+                let slope = self.radius / self.height;
+                let ring = slope * (self.height - local.y());
+                if radial > 0.0 {
+                    local.e[0] *= ring / radial;
+                    local.e[2] *= ring / radial;
+                }
+                // Gradient of x^2 + z^2 - k^2 (h - y)^2
+                let n = Vec3::new(local.x(), slope * ring, local.z());
+                if n.squared_length() > 0.0 { Vec3::unit_vector(&n) } else { Vec3::new(0.0, 1.0, 0.0) }
+            }
+            Part::Base => {
+                local.e[1] = 0.0;
+                Vec3::new(0.0, -1.0, 0.0)
+            }
+        };
+        let p = self.center + local;
+        let radial = Vec3::new(local.x(), 0.0, local.z());
+        let rho = radial.length();
+        let (v, dpdv) = match part {
+            // Moving up the side also shrinks the ring towards the apex
+            Part::Side => {
+                let inward = if rho > 0.0 { radial * (-self.radius / rho) } else { Vec3::default() };
+                (local.y() / self.height, inward + Vec3::new(0.0, self.height, 0.0))
+            }
+            Part::Base => {
+                let dpdv = if rho > 0.0 { radial * (self.radius / rho) } else { Vec3::default() };
+                (rho / self.radius, dpdv)
+            }
+        };
+
+        let mut rec = HitRecord {
+            t,
+            p,
+            p_error: local.abs() * gamma(5) + p.abs() * gamma(1),
+            u: local.z().atan2(local.x()) / (2.0 * PI) + 0.5,
+            v,
+            dpdu: Vec3::new(-local.z(), 0.0, local.x()) * (2.0 * PI),
+            dpdv,
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(r, outward_normal);
+        rec
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (hits, n) = self.intersections(r);
+        hits[..n]
+            .iter()
+            .filter(|(t, _)| *t > t_min && *t < t_max)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|&(t, part)| self.record(r, t, part))
+    }
+
+    fn hit_all(&self, r: &Ray) -> Vec<HitRecord> {
+        let (hits, n) = self.intersections(r);
+        let mut hits = hits[..n].to_vec();
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        hits.into_iter().map(|(t, part)| self.record(r, t, part)).collect()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        Some(Aabb::new(
+            self.center + Vec3::new(-r, 0.0, -r),
+            self.center + Vec3::new(r, self.height, r),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::{check_surface_hit, ray_through};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-9;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-5;
+
+    const CASES: usize = 1000;
+
+    fn cone() -> Cone {
+        Cone::new(Vec3::new(-1.0, 0.5, 0.0), 2.0, 4.0, Material::default())
+    }
+
+    fn inside(cone: &Cone, p: Vec3) -> bool {
+        let local = p - cone.center;
+        let ring = cone.radius / cone.height * (cone.height - local.y());
+        local.x() * local.x() + local.z() * local.z() <= ring * ring && (0.0..=cone.height).contains(&local.y())
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_the_side_or_the_base() {
+        let cone = cone();
+
+        // Halfway up the ring has radius 1 and the side leans in at slope 1/2
+        let across = Ray::new(Vec3::new(4.0, 2.5, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let rec = cone.hit(&across, 0.0, Float::MAX).unwrap();
+        assert!((rec.t - 4.0).abs() < TOLERANCE);
+        assert!(rec.front_face);
+        assert!((Vec3::from(rec.normal) - Vec3::new(2.0, 1.0, 0.0) / Float::sqrt(5.0)).length() < TOLERANCE);
+        assert!((rec.v - 0.5).abs() < TOLERANCE);
+
+        let down = Ray::new(Vec3::new(-0.5, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hits = cone.hit_all(&down);
+        assert_eq!(hits.len(), 2);
+        assert!((hits[0].t - 6.5).abs() < TOLERANCE && hits[0].front_face);
+        assert!((hits[1].t - 9.5).abs() < TOLERANCE && !hits[1].front_face);
+
+        let up = Ray::new(Vec3::new(-1.0, -2.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = cone.hit(&up, 0.0, Float::MAX).unwrap();
+        assert!((rec.t - 2.5).abs() < TOLERANCE);
+        assert!(rec.front_face);
+        assert!((Vec3::from(rec.normal) - Vec3::new(0.0, -1.0, 0.0)).length() < TOLERANCE);
+
+        let outside = Ray::new(Vec3::new(1.1, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(cone.hit(&outside, 0.0, Float::MAX).is_none());
+        assert!(cone.hit_all(&outside).is_empty());
+    }
+
+    #[test]
+    fn random_hits_lie_on_the_surface() {
+        let mut rng = StdRng::seed_from_u64(38);
+        let cone = cone();
+        let aabb = cone.bounding_box().unwrap();
+        let mut hits = 0;
+        for _ in 0..CASES {
+            let ray = ray_through(&mut rng, &aabb);
+            let all = cone.hit_all(&ray);
+            assert!(all.windows(2).all(|pair| pair[0].t <= pair[1].t));
+            for rec in &all {
+                check_surface_hit(&cone, &ray, rec, |p| inside(&cone, p));
+            }
+            let nearest = all.iter().find(|rec| rec.t > 0.0).map(|rec| rec.t);
+            assert_eq!(cone.hit(&ray, 0.0, Float::MAX).map(|rec| rec.t), nearest);
+            hits += all.len();
+        }
+        assert!(hits > CASES / 2);
+    }
+}
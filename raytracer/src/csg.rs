@@ -0,0 +1,207 @@
+use crate::float::Float;
+use crate::vec3::Vec3;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::aabb::Aabb;
+
+/// How the two operands of a `Csg` node are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+    /// Inside either operand.
+    Union,
+    /// Inside both operands.
+    Intersection,
+    /// Inside the first operand but not the second.
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+/// Constructive solid geometry node combining two closed objects. Both
+/// operands must implement `Hittable::hit_all`; nodes can be nested. Open
+/// surfaces such as `Plane` and `Quad` return no crossings from it, so as
+/// operands they act as empty space.
+pub struct Csg {
+    op: CsgOp,
+    a: Box<dyn Hittable>,
+    b: Box<dyn Hittable>,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Csg {
+        Csg { op, a, b }
+    }
+
+    pub fn union(a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Csg {
+        Csg::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection(a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Csg {
+        Csg::new(CsgOp::Intersection, a, b)
+    }
+
+    /// `a` with `b` carved out of it.
+    pub fn difference(a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Csg {
+        Csg::new(CsgOp::Difference, a, b)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        self.hit_all(r).into_iter().find(|rec| rec.t > t_min && rec.t < t_max)
+    }
+
+    /// Walks the crossings of both operands in order. The ray starts outside
+    /// of both, every crossing toggles one side, and a crossing belongs to
+    /// the result wherever it changes whether the ray is inside the result.
+    fn hit_all(&self, r: &Ray) -> Vec<HitRecord> {
+        let hits_a = self.a.hit_all(r);
+        let hits_b = self.b.hit_all(r);
+        let (mut i, mut j) = (0, 0);
+        let (mut in_a, mut in_b) = (false, false);
+        let mut inside = false;
+        let mut hits = Vec::new();
+
+        while i < hits_a.len() || j < hits_b.len() {
+            let from_a = j == hits_b.len() || (i < hits_a.len() && hits_a[i].t <= hits_b[j].t);
+            let mut rec = if from_a {
+                in_a = !in_a;
+                i += 1;
+                hits_a[i - 1].clone()
+            } else {
+                in_b = !in_b;
+                j += 1;
+                hits_b[j - 1].clone()
+            };
+
+            let now_inside = self.op.inside(in_a, in_b);
+            if now_inside != inside {
+                // Carved surfaces of `b` face the other way, so decide the
+                // side from the result rather than the operand
+                rec.front_face = now_inside;
+                hits.push(rec);
+                inside = now_inside;
+            }
+        }
+        hits
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self.op {
+            CsgOp::Union => Some(Aabb::surrounding(&self.a.bounding_box()?, &self.b.bounding_box()?)),
+            CsgOp::Intersection => match (self.a.bounding_box(), self.b.bounding_box()) {
+                (Some(a), Some(b)) => Some(Aabb::new(
+                    Vec3::new(a.min().x().max(b.min().x()), a.min().y().max(b.min().y()), a.min().z().max(b.min().z())),
+                    Vec3::new(a.max().x().min(b.max().x()), a.max().y().min(b.max().y()), a.max().z().min(b.max().z())),
+                )),
+                (a, b) => a.or(b),
+            },
+            CsgOp::Difference => self.a.bounding_box(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::material::Material;
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-9;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-5;
+
+    fn sphere(x: Float, radius: Float) -> Box<dyn Hittable> {
+        Box::new(Sphere::new(Vec3::new(x, 0.0, 0.0), radius, Material::default()))
+    }
+
+    fn cube() -> Box<dyn Hittable> {
+        Box::new(Cube::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0), Material::default()))
+    }
+
+    fn along_x(x: Float, y: Float) -> Ray {
+        Ray::new(Vec3::new(x, y, y), Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    /// Checks the crossings of `csg` in front of the ray against `expected`
+    /// entry/exit parameters, each flagged by whether it enters the result.
+    fn assert_hits(csg: &Csg, ray: &Ray, expected: &[(Float, bool)]) {
+        let hits: Vec<_> = csg.hit_all(ray).into_iter().filter(|rec| rec.t > 0.0).collect();
+        assert_eq!(hits.len(), expected.len(), "{:?}", hits.iter().map(|rec| rec.t).collect::<Vec<_>>());
+        for (rec, &(t, front_face)) in hits.iter().zip(expected) {
+            assert!((rec.t - t).abs() < TOLERANCE, "{} != {}", rec.t, t);
+            assert_eq!(rec.front_face, front_face, "at {}", t);
+            assert!(Vec3::dot(&ray.direction(), &rec.normal.into()) < 0.0);
+        }
+        match (csg.hit(ray, 0.0, Float::MAX), expected.first()) {
+            (Some(rec), Some(&(t, _))) => assert!((rec.t - t).abs() < TOLERANCE),
+            (rec, first) => assert!(rec.is_none() && first.is_none()),
+        }
+    }
+
+    #[test]
+    fn overlapping_spheres() {
+        // The left sphere spans x in [-1, 1], the right one [0, 2]
+        let ray = along_x(-5.0, 0.0);
+        assert_hits(&Csg::union(sphere(0.0, 1.0), sphere(1.0, 1.0)), &ray, &[(4.0, true), (7.0, false)]);
+        assert_hits(&Csg::intersection(sphere(0.0, 1.0), sphere(1.0, 1.0)), &ray, &[(5.0, true), (6.0, false)]);
+        assert_hits(&Csg::difference(sphere(0.0, 1.0), sphere(1.0, 1.0)), &ray, &[(4.0, true), (5.0, false)]);
+        // The ray enters through the carved surface, where it leaves the left sphere
+        let carved = Csg::difference(sphere(1.0, 1.0), sphere(0.0, 1.0));
+        assert_hits(&carved, &ray, &[(6.0, true), (7.0, false)]);
+        let rec = carved.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert!((Vec3::from(rec.normal) - Vec3::new(-1.0, 0.0, 0.0)).length() < TOLERANCE);
+    }
+
+    #[test]
+    fn cube_and_sphere() {
+        // The sphere sticks out of the faces but not the corners of the cube
+        let ray = along_x(-5.0, 0.0);
+        assert_hits(&Csg::union(cube(), sphere(0.0, 1.3)), &ray, &[(3.7, true), (6.3, false)]);
+        assert_hits(&Csg::intersection(cube(), sphere(0.0, 1.3)), &ray, &[(4.0, true), (6.0, false)]);
+        assert_hits(&Csg::difference(cube(), sphere(0.0, 1.3)), &ray, &[]);
+        assert_hits(
+            &Csg::difference(sphere(0.0, 1.3), cube()),
+            &ray,
+            &[(3.7, true), (4.0, false), (6.0, true), (6.3, false)],
+        );
+
+        let corner = along_x(-5.0, 0.95);
+        assert_hits(&Csg::difference(cube(), sphere(0.0, 1.3)), &corner, &[(4.0, true), (6.0, false)]);
+        assert_hits(&Csg::intersection(cube(), sphere(0.0, 1.3)), &corner, &[]);
+    }
+
+    #[test]
+    fn rays_starting_inside_an_operand() {
+        assert_hits(&Csg::union(sphere(0.0, 1.0), sphere(1.0, 1.0)), &along_x(0.0, 0.0), &[(2.0, false)]);
+        assert_hits(&Csg::intersection(sphere(0.0, 1.0), sphere(1.0, 1.0)), &along_x(0.5, 0.0), &[(0.5, false)]);
+        assert_hits(&Csg::difference(sphere(0.0, 1.0), sphere(1.0, 1.0)), &along_x(-0.5, 0.0), &[(0.5, false)]);
+        assert_hits(&Csg::difference(cube(), sphere(0.0, 1.3)), &along_x(0.0, 0.0), &[]);
+        // From inside the carved out part the first surface is the carved one
+        assert_hits(
+            &Csg::difference(sphere(1.0, 1.0), sphere(0.0, 1.0)),
+            &along_x(0.5, 0.0),
+            &[(0.5, true), (1.5, false)],
+        );
+    }
+
+    #[test]
+    fn open_operands_act_as_empty() {
+        let plane = || Box::new(Plane::new(Vec3::new(1.0, 0.0, 0.0), 0.0, Material::default()));
+        let ray = along_x(-5.0, 0.0);
+        assert_hits(&Csg::union(sphere(0.0, 1.0), plane()), &ray, &[(4.0, true), (6.0, false)]);
+        assert_hits(&Csg::difference(sphere(0.0, 1.0), plane()), &ray, &[(4.0, true), (6.0, false)]);
+        assert_hits(&Csg::intersection(sphere(0.0, 1.0), plane()), &ray, &[]);
+    }
+}
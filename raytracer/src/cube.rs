@@ -1,71 +1,102 @@
+use crate::float::{Float, gamma};
 use crate::vec3::Vec3;
 use crate::ray::Ray;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::aabb::Aabb;
 
-struct Aabb {
-    min: Vec3,
-    max: Vec3,
+/// Solid axis aligned box between two corners. Wrap it in a `Transform` to
+/// rotate it. UVs run from 0 to 1 across each face.
+pub struct Cube {
+    bounds: Aabb,
+    material: Material,
 }
 
-impl Hittable for Aabb {
-    fn hit(&self, ray: &Ray) -> bool {
-        let mut t_min = (self.min.x - ray.origin.x) / ray.direction.x;
-        let mut t_max = (self.max.x - ray.origin.x) / ray.direction.x;
-
-        if t_min > t_max {
-            std::mem::swap(&mut t_min, &mut t_max);
+impl Cube {
+    pub fn new(min: Vec3, max: Vec3, material: Material) -> Cube {
+        Cube {
+            bounds: Aabb::new(min, max),
+            material,
         }
+    }
 
-        let t_min = t_min.max((self.min.y - ray.origin.y) / ray.direction.y);
-        let t_max = t_max.min((self.max.y - ray.origin.y) / ray.direction.y);
-
-        if t_min > t_max {
-            return false;
+    /// Where the ray enters and leaves the box along its whole line, each
+    /// with the axis of the face it crosses.
+    fn intersections(&self, r: &Ray) -> Option<[(Float, usize); 2]> {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let mut near = (Float::MIN, 0);
+        let mut far = (Float::MAX, 0);
+        for axis in 0..3 {
+            let o = r.origin().e[axis];
+            let d = r.direction().e[axis];
+            if d == 0.0 {
+                // Parallel to this pair of faces, so it has to run between them
+                if o < min.e[axis] || o > max.e[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (min.e[axis] - o) / d;
+            let t1 = (max.e[axis] - o) / d;
+            let (t0, t1) = (t0.min(t1), t0.max(t1));
+            if t0 > near.0 {
+                near = (t0, axis);
+            }
+            if t1 < far.0 {
+                far = (t1, axis);
+            }
+        }
+        if near.0 > far.0 {
+            return None;
         }
+        Some([near, far])
+    }
 
-        let t_min = t_min.max((self.min.z - ray.origin.z) / ray.direction.z);
-        let t_max = t_max.min((self.max.z - ray.origin.z) / ray.direction.z);
+    /// Hit record at `t` on a face perpendicular to `axis`, snapped onto it.
+    fn record(&self, r: &Ray, t: Float, axis: usize) -> HitRecord {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let mut p = r.point_at_parameter(t);
+        let center = (min.e[axis] + max.e[axis]) / 2.0;
+        let mut outward_normal = Vec3::default();
+        if p.e[axis] < center {
+            p.e[axis] = min.e[axis];
+            outward_normal.e[axis] = -1.0;
+        } else {
+            p.e[axis] = max.e[axis];
+            outward_normal.e[axis] = 1.0;
+        }
 
-        t_max >= t_min
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut rec = HitRecord {
+            t,
+            p,
+            p_error: p.abs() * gamma(3),
+            u: (p.e[a] - min.e[a]) / (max.e[a] - min.e[a]),
+            v: (p.e[b] - min.e[b]) / (max.e[b] - min.e[b]),
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(r, outward_normal);
+        rec
     }
 }
 
-struct Cube {
-    bounds: Aabb,
-    material: Material,
-}
-
 impl Hittable for Cube {
-    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
-        if self.bounds.hit(ray) {
-            let t_min = (self.bounds.min.x - ray.origin.x) / ray.direction.x;
-            let t_max = (self.bounds.max.x - ray.origin.x) / ray.direction.x;
-
-            let t0 = t_min.min(t_max);
-            let t1 = t_min.max(t_max);
-
-            let t_min = (self.bounds.min.y - ray.origin.y) / ray.direction.y;
-            let t_max = (self.bounds.max.y - ray.origin.y) / ray.direction.y;
-
-            let t0 = t0.max(t_min.min(t_max));
-            let t1 = t1.min(t_min.max(t_max));
-
-            let t_min = (self.bounds.min.z - ray.origin.z) / ray.direction.z;
-            let t_max = (self.bounds.max.z - ray.origin.z) / ray.direction.z;
-
-            let t0 = t0.max(t_min.min(t_max));
-            let t1 = t1.min(t_min.max(t_max));
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        self.intersections(r)?
+            .into_iter()
+            .find(|&(t, _)| t > t_min && t < t_max)
+            .map(|(t, axis)| self.record(r, t, axis))
+    }
 
-            if t1 >= t0 {
-                return Some(HitRecord { 
-                    t: t0, 
-                    p: ray.point_at_parameter(t0), 
-                    normal: self.get_normal(hit_point), 
-                    material: self.material, 
-                });
-            }
+    fn hit_all(&self, r: &Ray) -> Vec<HitRecord> {
+        match self.intersections(r) {
+            Some(hits) => hits.iter().map(|&(t, axis)| self.record(r, t, axis)).collect(),
+            None => Vec::new(),
         }
-        None
     }
-}
\ No newline at end of file
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
@@ -0,0 +1,215 @@
+use crate::float::{Float, gamma};
+use crate::float::consts::PI;
+use crate::vec3::Vec3;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::roots::solve_quadratic;
+
+#[derive(Debug, Clone, Copy)]
+enum Part {
+    Side,
+    Bottom,
+    Top,
+}
+
+/// Closed cylinder standing upright on the centre of its base disk. Wrap it
+/// in a `Transform` to point it along another axis.
+pub struct Cylinder {
+    center: Vec3,
+    radius: Float,
+    height: Float,
+    material: Material,
+}
+
+impl Cylinder {
+    pub fn new(center: Vec3, radius: Float, height: Float, material: Material) -> Cylinder {
+        Cylinder {
+            center,
+            radius,
+            height,
+            material,
+        }
+    }
+
+    /// Surface hits along the whole ray, in no particular order.
+    fn intersections(&self, r: &Ray) -> ([(Float, Part); 4], usize) {
+        let o = r.origin() - self.center;
+        let d = r.direction();
+        let mut hits = [(0.0, Part::Side); 4];
+        let mut n = 0;
+
+        let a = d.x() * d.x() + d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z());
+        let c = o.x() * o.x() + o.z() * o.z() - self.radius * self.radius;
+        if a != 0.0 {
+            if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+                for t in [t0, t1] {
+                    let y = o.y() + t * d.y();
+                    if (0.0..=self.height).contains(&y) {
+                        hits[n] = (t, Part::Side);
+                        n += 1;
+                    }
+                }
+            }
+        }
+
+        if d.y() != 0.0 {
+            for (y, part) in [(0.0, Part::Bottom), (self.height, Part::Top)] {
+                let t = (y - o.y()) / d.y();
+                let (x, z) = (o.x() + t * d.x(), o.z() + t * d.z());
+                if x * x + z * z <= self.radius * self.radius {
+                    hits[n] = (t, part);
+                    n += 1;
+                }
+            }
+        }
+        (hits, n)
+    }
+
+    /// Hit record for `part` at `t`, with the point projected back onto it.
+    fn record(&self, r: &Ray, t: Float, part: Part) -> HitRecord {
+        let mut local = r.point_at_parameter(t) - self.center;
+        let outward_normal = match part {
+            Part::Side => {
+                let radial = Vec3::new(local.x(), 0.0, local.z()).length();
+                local.e[0] *= self.radius / radial;
+                local.e[2] *= self.radius / radial;
+                Vec3::new(local.x(), 0.0, local.z()) / self.radius
+            }
+            Part::Bottom => {
+                local.e[1] = 0.0;
+                Vec3::new(0.0, -1.0, 0.0)
+            }
+            Part::Top => {
+                local.e[1] = self.height;
+                Vec3::new(0.0, 1.0, 0.0)
+            }
+        };
+        let p = self.center + local;
+        let radial = Vec3::new(local.x(), 0.0, local.z());
+        let (v, dpdv) = match part {
+            Part::Side => (local.y() / self.height, Vec3::new(0.0, self.height, 0.0)),
+            _ => {
+                let rho = radial.length();
+                let dpdv = if rho > 0.0 { radial * (self.radius / rho) } else { Vec3::default() };
+                (rho / self.radius, dpdv)
+            }
+        };
+
+        let mut rec = HitRecord {
+            t,
+            p,
+            p_error: local.abs() * gamma(5) + p.abs() * gamma(1),
+            u: local.z().atan2(local.x()) / (2.0 * PI) + 0.5,
+            v,
+            dpdu: Vec3::new(-local.z(), 0.0, local.x()) * (2.0 * PI),
+            dpdv,
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(r, outward_normal);
+        rec
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (hits, n) = self.intersections(r);
+        hits[..n]
+            .iter()
+            .filter(|(t, _)| *t > t_min && *t < t_max)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|&(t, part)| self.record(r, t, part))
+    }
+
+    fn hit_all(&self, r: &Ray) -> Vec<HitRecord> {
+        let (hits, n) = self.intersections(r);
+        let mut hits = hits[..n].to_vec();
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        hits.into_iter().map(|(t, part)| self.record(r, t, part)).collect()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        Some(Aabb::new(
+            self.center + Vec3::new(-r, 0.0, -r),
+            self.center + Vec3::new(r, self.height, r),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::{check_surface_hit, ray_through};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-9;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-5;
+
+    const CASES: usize = 1000;
+
+    fn cylinder() -> Cylinder {
+        Cylinder::new(Vec3::new(1.0, -1.0, 2.0), 1.5, 3.0, Material::default())
+    }
+
+    fn inside(cylinder: &Cylinder, p: Vec3) -> bool {
+        let local = p - cylinder.center;
+        local.x() * local.x() + local.z() * local.z() <= cylinder.radius * cylinder.radius
+            && (0.0..=cylinder.height).contains(&local.y())
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_the_side_or_the_caps() {
+        let cylinder = cylinder();
+
+        let across = Ray::new(Vec3::new(-4.0, 0.5, 2.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = cylinder.hit(&across, 0.0, Float::MAX).unwrap();
+        assert!((rec.t - 3.5).abs() < TOLERANCE);
+        assert!(rec.front_face);
+        assert!((Vec3::from(rec.normal) - Vec3::new(-1.0, 0.0, 0.0)).length() < TOLERANCE);
+
+        let down = Ray::new(Vec3::new(1.5, 5.0, 2.0), Vec3::new(0.0, -1.0, 0.0));
+        let hits: Vec<_> = cylinder.hit_all(&down).iter().map(|rec| (rec.t, rec.front_face)).collect();
+        assert_eq!(hits, [(3.0, true), (6.0, false)]);
+        let rec = cylinder.hit(&down, 0.0, Float::MAX).unwrap();
+        assert!((Vec3::from(rec.normal) - Vec3::new(0.0, 1.0, 0.0)).length() < TOLERANCE);
+        assert!((rec.v - 1.0 / 3.0).abs() < TOLERANCE);
+
+        // From inside the normal still faces the ray, against the outward one
+        let up = Ray::new(Vec3::new(1.0, 0.0, 2.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = cylinder.hit(&up, 0.0, Float::MAX).unwrap();
+        assert!((rec.t - 2.0).abs() < TOLERANCE);
+        assert!(!rec.front_face);
+        assert!((Vec3::from(rec.normal) - Vec3::new(0.0, -1.0, 0.0)).length() < TOLERANCE);
+
+        let outside = Ray::new(Vec3::new(2.6, 5.0, 2.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(cylinder.hit(&outside, 0.0, Float::MAX).is_none());
+        assert!(cylinder.hit_all(&outside).is_empty());
+    }
+
+    #[test]
+    fn random_hits_lie_on_the_surface() {
+        let mut rng = StdRng::seed_from_u64(38);
+        let cylinder = cylinder();
+        let aabb = cylinder.bounding_box().unwrap();
+        let mut hits = 0;
+        for _ in 0..CASES {
+            let ray = ray_through(&mut rng, &aabb);
+            let all = cylinder.hit_all(&ray);
+            assert!(all.windows(2).all(|pair| pair[0].t <= pair[1].t));
+            for rec in &all {
+                check_surface_hit(&cylinder, &ray, rec, |p| inside(&cylinder, p));
+            }
+            let nearest = all.iter().find(|rec| rec.t > 0.0).map(|rec| rec.t);
+            assert_eq!(cylinder.hit(&ray, 0.0, Float::MAX).map(|rec| rec.t), nearest);
+            hits += all.len();
+        }
+        assert!(hits > CASES);
+    }
+}
@@ -0,0 +1,285 @@
+//! Light arriving from infinitely far away, seen wherever a ray escapes the
+//! scene.
+
+use std::sync::Arc;
+
+use crate::float::Float;
+use crate::float::consts::PI;
+use crate::sampler::sample_unit_sphere;
+use crate::sky::Sky;
+use crate::texture::Image;
+use crate::vec3::Vec3;
+
+#[derive(Debug, Clone)]
+pub enum Environment {
+    Constant(Vec3),
+    /// Blend over the height of the direction, from `bottom` straight down
+    /// to `top` straight up.
+    Gradient { bottom: Vec3, top: Vec3 },
+    /// Equirectangular image with up at the top, usually HDR.
+    Map(Arc<EnvironmentMap>),
+    /// Analytic daylight with a sun.
+    Sky(Arc<Sky>),
+}
+
+impl Default for Environment {
+    /// The white to blue sky the renderer has always used.
+    fn default() -> Self {
+        Environment::Gradient { bottom: Vec3::new(1.0, 1.0, 1.0), top: Vec3::new(0.5, 0.7, 1.0) }
+    }
+}
+
+impl Environment {
+    /// Radiance arriving from `direction`, which need not be normalized.
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        match *self {
+            Environment::Constant(radiance) => radiance,
+            Environment::Gradient { bottom, top } => {
+                let t = 0.5 * (Vec3::unit_vector(&direction).y() + 1.0);
+                bottom * (1.0 - t) + top * t
+            }
+            Environment::Map(ref map) => map.radiance(direction),
+            Environment::Sky(ref sky) => sky.radiance(direction),
+        }
+    }
+
+    /// Picks a direction to look for light in, with the radiance from it and
+    /// the density per unit solid angle.
+    pub fn sample(&self, u: (Float, Float)) -> (Vec3, Vec3, Float) {
+        match *self {
+            Environment::Map(ref map) => map.sample(u),
+            Environment::Sky(ref sky) => sky.sample(u),
+            _ => {
+                let direction = sample_unit_sphere(u);
+                (direction, self.radiance(direction), 1.0 / (4.0 * PI))
+            }
+        }
+    }
+
+    /// Density of `sample` returning `direction`.
+    pub fn pdf(&self, direction: Vec3) -> Float {
+        match *self {
+            Environment::Map(ref map) => map.pdf(direction),
+            Environment::Sky(ref sky) => sky.pdf(direction),
+            _ => 1.0 / (4.0 * PI),
+        }
+    }
+}
+
+/// Piecewise constant density over `[0, 1)` with one piece per value.
+#[derive(Debug)]
+struct Distribution1D {
+    func: Vec<Float>,
+    cdf: Vec<Float>,
+    /// Integral of `func` over `[0, 1)`.
+    integral: Float,
+}
+
+impl Distribution1D {
+    fn new(func: Vec<Float>) -> Distribution1D {
+        let n = func.len() as Float;
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.0);
+        for (i, f) in func.iter().enumerate() {
+            cdf.push(cdf[i] + f / n);
+        }
+        let integral = cdf[func.len()];
+        if integral > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            // All zero: fall back to uniform
+            cdf.iter_mut().enumerate().for_each(|(i, c)| *c = i as Float / n);
+        }
+        Distribution1D { func, cdf, integral }
+    }
+
+    /// Density at piece `i`, relative to the uniform one.
+    fn pdf(&self, i: usize) -> Float {
+        if self.integral > 0.0 { self.func[i] / self.integral } else { 1.0 }
+    }
+
+    /// Point in `[0, 1)` distributed like `func`, with its piece and density.
+    fn sample(&self, u: Float) -> (Float, usize, Float) {
+        let i = self.cdf.partition_point(|&c| c <= u).clamp(1, self.func.len()) - 1;
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let offset = if width > 0.0 { (u - self.cdf[i]) / width } else { 0.0 };
+        let x = (i as Float + offset.clamp(0.0, 1.0)) / self.func.len() as Float;
+        (x.min(1.0 - Float::EPSILON), i, self.pdf(i))
+    }
+}
+
+/// Equirectangular environment image with a density over its texels
+/// proportional to their luminance, for sampling the bright parts.
+#[derive(Debug)]
+pub struct EnvironmentMap {
+    image: Image,
+    /// Which row, then which texel in it.
+    rows: Distribution1D,
+    columns: Vec<Distribution1D>,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Image) -> EnvironmentMap {
+        let (width, height) = (image.width(), image.height());
+        // Rows near the poles cover less of the sphere, weigh them by sin(theta)
+        let columns: Vec<Distribution1D> = (0..height)
+            .map(|y| {
+                let sin_theta = (PI * (y as Float + 0.5) / height as Float).sin();
+                let row = (0..width).map(|x| image.texel(x as i64, y as i64).luminance().max(0.0) * sin_theta).collect();
+                Distribution1D::new(row)
+            })
+            .collect();
+        let rows = Distribution1D::new(columns.iter().map(|c| c.integral).collect());
+        EnvironmentMap { image, rows, columns }
+    }
+
+    /// Image coordinates of `direction`: u around the y axis, v from the
+    /// bottom up, matching `Image::lookup`.
+    fn uv(direction: Vec3) -> (Float, Float) {
+        let d = Vec3::unit_vector(&direction);
+        // Not acos(y), which loses most of theta near the poles
+        let theta = d.x().hypot(d.z()).atan2(d.y());
+        let phi = (-d.z()).atan2(d.x());
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+        (phi / (2.0 * PI), 1.0 - theta / PI)
+    }
+
+    fn direction(u: Float, v: Float) -> Vec3 {
+        let (theta, phi) = (PI * (1.0 - v), 2.0 * PI * u);
+        Vec3::new(theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin())
+    }
+
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        let (u, v) = EnvironmentMap::uv(direction);
+        self.image.lookup(u, v)
+    }
+
+    pub fn sample(&self, u: (Float, Float)) -> (Vec3, Vec3, Float) {
+        let (y, row, row_pdf) = self.rows.sample(u.0);
+        let (x, _, column_pdf) = self.columns[row].sample(u.1);
+        let direction = EnvironmentMap::direction(x, 1.0 - y);
+        // From density over the image to density over the sphere
+        let sin_theta = (PI * y).sin();
+        if sin_theta <= 0.0 {
+            return (direction, Vec3::default(), 0.0);
+        }
+        let pdf = row_pdf * column_pdf / (2.0 * PI * PI * sin_theta);
+        (direction, self.radiance(direction), pdf)
+    }
+
+    pub fn pdf(&self, direction: Vec3) -> Float {
+        let (u, v) = EnvironmentMap::uv(direction);
+        let sin_theta = (PI * (1.0 - v)).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let (width, height) = (self.image.width(), self.image.height());
+        let x = ((u * width as Float) as usize).min(width - 1);
+        let y = (((1.0 - v) * height as Float) as usize).min(height - 1);
+        self.rows.pdf(y) * self.columns[y].pdf(x) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const CASES: usize = 1000;
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-6;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-3;
+
+    /// Random map with a bright spot, and black where `black_row` says so.
+    fn random_map(rng: &mut StdRng, width: usize, height: usize, black_row: impl Fn(usize) -> bool) -> EnvironmentMap {
+        let texels = (0..width * height)
+            .map(|i| {
+                if black_row(i / width) {
+                    Vec3::default()
+                } else if i == width * height / 3 {
+                    Vec3::new(50.0, 40.0, 30.0)
+                } else {
+                    Vec3::new(rng.gen(), rng.gen(), rng.gen())
+                }
+            })
+            .collect();
+        EnvironmentMap::new(Image::new(width, height, texels))
+    }
+
+    /// Integral of the map's density over the sphere, on a grid aligned with
+    /// the texels so the density is constant in every cell.
+    fn integrate_pdf(map: &EnvironmentMap) -> Float {
+        let (width, height) = (map.image.width() * 4, map.image.height() * 4);
+        let (d_theta, d_phi) = (PI / height as Float, 2.0 * PI / width as Float);
+        let mut sum = 0.0;
+        for y in 0..height {
+            let theta = (y as Float + 0.5) * d_theta;
+            for x in 0..width {
+                let phi = (x as Float + 0.5) * d_phi;
+                let direction = Vec3::new(theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin());
+                sum += map.pdf(direction) * theta.sin() * d_theta * d_phi;
+            }
+        }
+        sum
+    }
+
+    fn assert_sample_matches_pdf(map: &EnvironmentMap, rng: &mut StdRng) {
+        for _ in 0..CASES {
+            let (direction, radiance, pdf) = map.sample((rng.gen(), rng.gen()));
+            assert!(pdf > 0.0, "sampled {:?} with pdf {}", direction.e, pdf);
+            assert!((pdf - map.pdf(direction)).abs() <= TOLERANCE * pdf, "{} != {}", pdf, map.pdf(direction));
+            assert_eq!(radiance.e, map.radiance(direction).e);
+        }
+    }
+
+    #[test]
+    fn sample_pdf_matches_pdf() {
+        let mut rng = StdRng::seed_from_u64(48);
+        let map = random_map(&mut rng, 16, 8, |_| false);
+        assert_sample_matches_pdf(&map, &mut rng);
+
+        let env = Environment::Map(Arc::new(map));
+        for _ in 0..CASES {
+            let (direction, _, pdf) = env.sample((rng.gen(), rng.gen()));
+            assert!((pdf - env.pdf(direction)).abs() <= TOLERANCE * pdf);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one_over_the_sphere() {
+        let mut rng = StdRng::seed_from_u64(49);
+        let map = random_map(&mut rng, 16, 8, |_| false);
+        assert!((integrate_pdf(&map) - 1.0).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn black_rows_are_never_sampled() {
+        let mut rng = StdRng::seed_from_u64(50);
+        // Black ground and a black band across the sky
+        let black_row = |y: usize| y >= 4 || y == 1;
+        let map = random_map(&mut rng, 16, 8, black_row);
+        assert!((integrate_pdf(&map) - 1.0).abs() < TOLERANCE);
+        assert_sample_matches_pdf(&map, &mut rng);
+        for _ in 0..CASES {
+            let (direction, _, _) = map.sample((rng.gen(), rng.gen()));
+            let (_, v) = EnvironmentMap::uv(direction);
+            let row = (((1.0 - v) * 8.0) as usize).min(7);
+            assert!(!black_row(row), "sampled black row {}", row);
+        }
+        assert_eq!(map.pdf(Vec3::new(0.3, -1.0, 0.2)), 0.0);
+    }
+
+    #[test]
+    fn black_map_samples_uniformly_over_the_image() {
+        let mut rng = StdRng::seed_from_u64(51);
+        let map = random_map(&mut rng, 16, 8, |_| true);
+        assert!((integrate_pdf(&map) - 1.0).abs() < TOLERANCE);
+        assert_sample_matches_pdf(&map, &mut rng);
+
+        let uniform = Distribution1D::new(vec![0.0; 4]);
+        assert_eq!(uniform.pdf(2), 1.0);
+        assert_eq!(uniform.sample(0.6).1, 2);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::float::Float;
+use crate::float::consts::PI;
+
+/// Pixel reconstruction filter. Every sample is splatted onto all pixels
+/// whose centre lies within `radius` of it, weighted by the filter.
+#[derive(Debug, Clone, Copy)]
+pub enum Filter {
+    Box { radius: Float },
+    Tent { radius: Float },
+    Gaussian { radius: Float, alpha: Float },
+    Mitchell { radius: Float, b: Float, c: Float },
+    Lanczos { radius: Float, tau: Float },
+}
+
+impl Default for Filter {
+    /// A box of half a pixel, i.e. plain per-pixel averaging.
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    /// Looks a filter up by name with commonly used parameters.
+    pub fn from_name(name: &str) -> Option<Filter> {
+        match name {
+            "box" => Some(Filter::Box { radius: 0.5 }),
+            "tent" => Some(Filter::Tent { radius: 1.0 }),
+            "gaussian" => Some(Filter::Gaussian { radius: 1.5, alpha: 2.0 }),
+            "mitchell" => Some(Filter::Mitchell { radius: 2.0, b: 1.0 / 3.0, c: 1.0 / 3.0 }),
+            "lanczos" => Some(Filter::Lanczos { radius: 3.0, tau: 3.0 }),
+            _ => None,
+        }
+    }
+
+    pub fn radius(&self) -> Float {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius, .. } => radius,
+        }
+    }
+
+    /// Weight of a sample at offset (x, y) from a pixel centre. All filters
+    /// are separable, so this is the product of the 1D weights.
+    pub fn evaluate(&self, x: Float, y: Float) -> Float {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: Float) -> Float {
+        let x = x.abs();
+        if x > self.radius() {
+            return 0.0;
+        }
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => radius - x,
+            Filter::Gaussian { radius, alpha } => {
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => mitchell_1d(2.0 * x / radius, b, c),
+            Filter::Lanczos { tau, .. } => sinc(x) * sinc(x / tau),
+        }
+    }
+}
+
+/// Mitchell-Netravali cubic over [0, 2].
+fn mitchell_1d(x: Float, b: Float, c: Float) -> Float {
+    if x < 1.0 {
+        ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b))
+            / 6.0
+    } else {
+        ((-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    }
+}
+
+fn sinc(x: Float) -> Float {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: [&str; 5] = ["box", "tent", "gaussian", "mitchell", "lanczos"];
+    const TOLERANCE: Float = 1e-5;
+
+    #[test]
+    fn filters_vanish_outside_their_radius() {
+        for name in NAMES {
+            let filter = Filter::from_name(name).unwrap();
+            let r = filter.radius();
+            for outside in [r + 1e-3, r + 0.5, 2.0 * r, 100.0] {
+                assert_eq!(filter.evaluate(outside, 0.0), 0.0, "{} at x = {}", name, outside);
+                assert_eq!(filter.evaluate(0.0, -outside), 0.0, "{} at y = {}", name, -outside);
+                assert_eq!(filter.evaluate(outside, outside), 0.0, "{} on the diagonal", name);
+            }
+            assert!(filter.evaluate(0.0, 0.0) > 0.0, "{} at the centre", name);
+        }
+        assert!(Filter::from_name("sinc").is_none());
+    }
+
+    #[test]
+    fn mitchell_known_values() {
+        // B = C = 1/3 over radius 2: (6 - 2B) / 6 at 0 and B / 6 at 1
+        let mitchell = Filter::from_name("mitchell").unwrap();
+        assert!((mitchell.evaluate_1d(0.0) - 8.0 / 9.0).abs() < TOLERANCE);
+        assert!((mitchell.evaluate_1d(1.0) - 1.0 / 18.0).abs() < TOLERANCE);
+        assert!((mitchell.evaluate_1d(-1.0) - 1.0 / 18.0).abs() < TOLERANCE);
+        assert!(mitchell.evaluate_1d(2.0).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn lanczos_known_values() {
+        let lanczos = Filter::from_name("lanczos").unwrap();
+        assert!((lanczos.evaluate_1d(0.0) - 1.0).abs() < TOLERANCE);
+        for x in [1.0, 2.0, -1.0] {
+            assert!(lanczos.evaluate_1d(x).abs() < TOLERANCE, "lanczos at {}", x);
+        }
+        // Negative lobe between the first two zeros
+        assert!(lanczos.evaluate_1d(1.5) < 0.0);
+    }
+}
@@ -0,0 +1,30 @@
+//! Floating point type used for all geometry and color math. It is `f64`
+//! unless the crate is built with the `f32` feature, which trades precision
+//! for speed and memory on big scenes.
+
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+#[cfg(all(feature = "simd", not(feature = "f32")))]
+pub type FloatX4 = wide::f64x4;
+
+#[cfg(feature = "f32")]
+pub type Float = f32;
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+#[cfg(all(feature = "simd", feature = "f32"))]
+pub type FloatX4 = wide::f32x4;
+
+/// Largest value below one, so samples stay inside `[0, 1)` after rounding.
+pub const ONE_MINUS_EPSILON: Float = 1.0 - Float::EPSILON / 2.0;
+
+/// Bound on the relative rounding error of a single operation.
+pub const MACHINE_EPSILON: Float = Float::EPSILON * 0.5;
+
+/// Bound on the relative error accumulated over `n` operations (Higham's
+/// gamma, as used in PBRT's floating point error analysis).
+pub fn gamma(n: u32) -> Float {
+    let n = n as Float * MACHINE_EPSILON;
+    n / (1.0 - n)
+}
@@ -0,0 +1,181 @@
+use crate::float::Float;
+use crate::filter::Filter;
+use crate::vec3::Vec3;
+
+/// Accumulated radiance and filter weight of one pixel.
+#[derive(Debug, Clone, Copy, Default)]
+struct Pixel {
+    sum: Vec3,
+    weight: Float,
+}
+
+/// Full-resolution image that filtered samples are accumulated into.
+///
+/// Raster coordinates have their origin in the top left corner with y
+/// pointing down, and pixel (x, y) has its centre at (x + 0.5, y + 0.5).
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Pixel>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Pixel::default(); (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Creates a tile for rendering the pixels in `[x0, x1) x [y0, y1)`.
+    /// The tile extends past those bounds by the filter radius so samples
+    /// near its edge can be splatted onto neighbouring pixels.
+    pub fn tile(&self, x0: u32, y0: u32, x1: u32, y1: u32, filter: Filter) -> FilmTile {
+        let r = filter.radius();
+        let tx0 = (x0 as Float - r + 0.5).floor().max(0.0) as u32;
+        let ty0 = (y0 as Float - r + 0.5).floor().max(0.0) as u32;
+        let tx1 = ((x1 as Float + r - 0.5).ceil() as u32).min(self.width);
+        let ty1 = ((y1 as Float + r - 0.5).ceil() as u32).min(self.height);
+        FilmTile {
+            x0: tx0,
+            y0: ty0,
+            x1: tx1.max(tx0),
+            y1: ty1.max(ty0),
+            filter,
+            pixels: vec![Pixel::default(); ((tx1.max(tx0) - tx0) * (ty1.max(ty0) - ty0)) as usize],
+        }
+    }
+
+    /// Adds the contents of a finished tile to the image.
+    pub fn merge_tile(&mut self, tile: FilmTile) {
+        let tile_width = tile.x1 - tile.x0;
+        for (i, pixel) in tile.pixels.iter().enumerate() {
+            let x = tile.x0 + i as u32 % tile_width;
+            let y = tile.y0 + i as u32 / tile_width;
+            let target = &mut self.pixels[(y * self.width + x) as usize];
+            target.sum += pixel.sum;
+            target.weight += pixel.weight;
+        }
+    }
+
+    /// Copies the pixels in `[x0, x1) x [y0, y1)` into a framebuffer of their own.
+    pub fn crop(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> Framebuffer {
+        let (x1, y1) = (x1.min(self.width).max(x0), y1.min(self.height).max(y0));
+        let mut cropped = Framebuffer::new(x1 - x0, y1 - y0);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                cropped.pixels[((y - y0) * cropped.width + (x - x0)) as usize] = self.pixels[(y * self.width + x) as usize];
+            }
+        }
+        cropped
+    }
+
+    /// Filtered radiance of a pixel.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Vec3 {
+        let pixel = self.pixels[(y * self.width + x) as usize];
+        if pixel.weight == 0.0 {
+            return Vec3::default();
+        }
+        pixel.sum / pixel.weight
+    }
+
+    /// Gamma corrected (gamma 2) rows of 0-255 colors, top row first, as
+    /// expected by `gen_ppm`.
+    pub fn to_rows(&self) -> Vec<Vec<Vec3>> {
+        (0..self.height).map(|y| {
+            (0..self.width).map(|x| {
+                let c = self.get_pixel(x, y);
+                // Negative filter lobes can push dark pixels below zero
+                let mut color_vector = 255.99 * Vec3::new(c.x().max(0.0).sqrt(), c.y().max(0.0).sqrt(), c.z().max(0.0).sqrt());
+                color_vector.colorize();
+                color_vector
+            }).collect()
+        }).collect()
+    }
+}
+
+/// Part of a `Framebuffer` that one thread renders into on its own.
+pub struct FilmTile {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+    filter: Filter,
+    pixels: Vec<Pixel>,
+}
+
+impl FilmTile {
+    /// Splats a sample taken at raster position (film_x, film_y) onto every
+    /// pixel of the tile within the filter radius.
+    pub fn add_sample(&mut self, film_x: Float, film_y: Float, color: Vec3) {
+        let r = self.filter.radius();
+        let px0 = ((film_x - r - 0.5).ceil().max(self.x0 as Float)) as u32;
+        let py0 = ((film_y - r - 0.5).ceil().max(self.y0 as Float)) as u32;
+        let px1 = ((film_x + r - 0.5).floor() + 1.0).min(self.x1 as Float).max(0.0) as u32;
+        let py1 = ((film_y + r - 0.5).floor() + 1.0).min(self.y1 as Float).max(0.0) as u32;
+
+        let tile_width = self.x1 - self.x0;
+        for y in py0..py1 {
+            for x in px0..px1 {
+                let weight = self.filter.evaluate(x as Float + 0.5 - film_x, y as Float + 0.5 - film_y);
+                if weight == 0.0 {
+                    continue;
+                }
+                let pixel = &mut self.pixels[((y - self.y0) * tile_width + (x - self.x0)) as usize];
+                pixel.sum += weight * color;
+                pixel.weight += weight;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Framebuffer with one sample splatted at every corner of the image
+    /// and just outside each of its edges, through tiles at the borders.
+    fn splat_edges(filter: Filter) -> Framebuffer {
+        let (width, height) = (5, 4);
+        let mut framebuffer = Framebuffer::new(width, height);
+        let positions = [(0.01, 0.01), (4.99, 0.01), (0.01, 3.99), (4.99, 3.99), (2.5, 0.0), (0.0, 2.0), (5.0, 2.0), (2.5, 4.0)];
+        for (x0, y0, x1, y1) in [(0, 0, 3, 2), (3, 0, 5, 2), (0, 2, 3, 4), (3, 2, 5, 4)] {
+            let mut tile = framebuffer.tile(x0, y0, x1, y1, filter);
+            assert!(tile.x1 <= width && tile.y1 <= height);
+            for (x, y) in positions {
+                tile.add_sample(x, y, Vec3::new(1.0, 1.0, 1.0));
+            }
+            framebuffer.merge_tile(tile);
+        }
+        framebuffer
+    }
+
+    #[test]
+    fn splats_stay_inside_the_image() {
+        for name in ["box", "tent", "gaussian", "mitchell", "lanczos"] {
+            let framebuffer = splat_edges(Filter::from_name(name).unwrap());
+            assert_eq!(framebuffer.pixels.len(), 20);
+            // Every corner pixel saw the sample right next to it
+            for (x, y) in [(0, 0), (4, 0), (0, 3), (4, 3)] {
+                assert!(framebuffer.pixels[(y * 5 + x) as usize].weight != 0.0, "{} corner ({}, {})", name, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn crop_clamps_to_the_image() {
+        let framebuffer = splat_edges(Filter::default());
+        let cropped = framebuffer.crop(3, 2, 10, 10);
+        assert_eq!((cropped.width(), cropped.height()), (2, 2));
+        assert_eq!(cropped.get_pixel(1, 1).e, framebuffer.get_pixel(4, 3).e);
+    }
+}
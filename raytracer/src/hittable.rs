@@ -1,26 +1,96 @@
-use crate::vec3::Vec3;
-use crate::ray::Ray;
+use crate::float::Float;
+use crate::vec3::{Normal3, Vec3};
+use crate::ray::{Ray, offset_ray_origin};
 use crate::material::Material;
+use crate::aabb::Aabb;
+
+use std::sync::Arc;
 
 //p = Point
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone)]
 pub struct HitRecord {
-    pub t: f64,
+    pub t: Float,
     pub p: Vec3,
-    pub normal: Vec3,
+    /// Absolute bound on the rounding error in each coordinate of `p`.
+    pub p_error: Vec3,
+    /// Surface normal, always facing against the incoming ray.
+    pub normal: Normal3,
+    /// Normal used for shading, on the same side as `normal`. Normal and
+    /// bump maps tilt it to fake detail the geometry does not have.
+    pub shading_normal: Normal3,
+    /// Whether the ray hit the outside of the surface, i.e. the side the
+    /// outward normal points to.
+    pub front_face: bool,
+    /// Surface coordinates of the hit, each in `[0, 1]` for finite shapes.
+    pub u: Float,
+    pub v: Float,
+    /// Rate of change of `p` along `u` and `v`, zero for shapes without a
+    /// parametrization.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
     pub material: Material,
 }
 
 pub trait Hittable: Sync + Send {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, _r: &Ray, _t_min: Float, _t_max: Float) -> Option<HitRecord> {
+        None
+    }
+
+    /// Box enclosing the object, or `None` if it is unbounded like an infinite plane.
+    fn bounding_box(&self) -> Option<Aabb> {
         None
     }
 
+    /// Every crossing of the surface along the whole line of the ray,
+    /// including behind its origin, sorted by `t`. Only closed objects can
+    /// answer this; `Csg` needs it and treats an empty list as a miss.
+    fn hit_all(&self, _r: &Ray) -> Vec<HitRecord> {
+        Vec::new()
+    }
+
+    /// Fraction of light that makes it along the ray between `t_min` and
+    /// `t_max`. Solid objects block everything they intersect; volumes let
+    /// part of it through.
+    fn transmittance(&self, r: &Ray, t_min: Float, t_max: Float) -> Float {
+        if self.hit(r, t_min, t_max).is_some() { 0.0 } else { 1.0 }
+    }
+}
+
+/// Lets one object be shared by many instances, e.g. several `Transform`s of one mesh.
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn hit_all(&self, r: &Ray) -> Vec<HitRecord> {
+        (**self).hit_all(r)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: Float, t_max: Float) -> Float {
+        (**self).transmittance(r, t_min, t_max)
+    }
 }
 
 impl HitRecord {
-    
-    pub fn t(&self) -> f64 {
+    /// Stores `outward_normal` flipped to face against `r`, and which side was hit.
+    pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
+        let outward_normal = Normal3::new(outward_normal);
+        self.front_face = Vec3::dot(&r.direction(), &outward_normal.into()) < 0.0;
+        self.normal = if self.front_face { outward_normal } else { -outward_normal };
+        self.shading_normal = self.normal;
+    }
+
+    /// Ray leaving the hit point in `direction`, with its origin pushed off
+    /// the surface by the error in `p` so it cannot hit the surface again.
+    pub fn spawn_ray(&self, direction: Vec3) -> Ray {
+        Ray::new(offset_ray_origin(self.p, self.p_error, self.normal.into(), direction), direction)
+    }
+
+    pub fn t(&self) -> Float {
         self.t
     }
 
@@ -28,15 +98,15 @@ impl HitRecord {
         self.p
     }
 
-    pub fn normal(&self) -> Vec3 {
+    pub fn normal(&self) -> Normal3 {
         self.normal
     }
 
     pub fn material(&self) -> Material {
-        self.material
+        self.material.clone()
     }
 
-    pub fn set_t(&mut self, val: f64) {
+    pub fn set_t(&mut self, val: Float) {
         self.t = val
     }
 
@@ -44,11 +114,81 @@ impl HitRecord {
         self.p = val
     }
 
-    pub fn set_normal(&mut self, val: Vec3) {
+    pub fn set_normal(&mut self, val: Normal3) {
         self.normal = val
     }
 
     pub fn set_material(&mut self, val: Material) {
         self.material = val
     }
-}
\ No newline at end of file
+}
+/// Checks shared by the tests of the individual shapes.
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::Rng;
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-6;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-4;
+
+    /// Step along `dpdu` and `dpdv` when comparing them with the change in UV.
+    const DELTA: Float = 1e-4;
+
+    /// Ray from a random point around `aabb` through a random point inside it.
+    pub(crate) fn ray_through(rng: &mut StdRng, aabb: &Aabb) -> Ray {
+        let point = |rng: &mut StdRng, grow: Float| {
+            let mut p = Vec3::default();
+            for i in 0..3 {
+                let (lo, hi) = (aabb.min().e[i], aabb.max().e[i]);
+                let pad = (hi - lo) * grow;
+                p.e[i] = rng.gen_range(lo - pad..hi + pad);
+            }
+            p
+        };
+        let origin = point(rng, 1.0);
+        let target = point(rng, 0.0);
+        Ray::new(origin, (target - origin) * rng.gen_range(0.5..2.0))
+    }
+
+    /// Difference of two wrapping coordinates, in `[-0.5, 0.5)`.
+    fn wrapped(a: Float, b: Float) -> Float {
+        (a - b + 0.5).rem_euclid(1.0) - 0.5
+    }
+
+    /// Checks a hit of `shape` by `ray`, whose interior is `inside`: the point
+    /// is on the ray and in the bounding box, the normal faces against the
+    /// ray, the UVs are in `[0, 1]`, the outward normal leaves
+    /// the interior and stepping along `dpdu` and `dpdv` moves the UVs by the
+    /// same amount. Both UVs are compared modulo 1 as they wrap on closed shapes.
+    pub(crate) fn check_surface_hit(shape: &dyn Hittable, ray: &Ray, rec: &HitRecord, inside: impl Fn(Vec3) -> bool) {
+        let scale = rec.p.length().max(1.0);
+        assert!((ray.point_at_parameter(rec.t) - rec.p).length() <= 1e2 * TOLERANCE * scale);
+        assert!(Vec3::dot(&ray.direction(), &rec.normal.into()) <= 0.0);
+        assert!((0.0..=1.0).contains(&rec.u) && (0.0..=1.0).contains(&rec.v), "uv {} {}", rec.u, rec.v);
+        let aabb = shape.bounding_box().unwrap();
+        for i in 0..3 {
+            assert!(aabb.min().e[i] - TOLERANCE * scale <= rec.p.e[i] && rec.p.e[i] <= aabb.max().e[i] + TOLERANCE * scale);
+        }
+
+        let normal = Vec3::from(rec.normal);
+        let outward = if rec.front_face { normal } else { -normal };
+        assert!(inside(rec.p - outward * 1e-3), "{:?} not inside", rec.p - outward * 1e-3);
+        assert!(!inside(rec.p + outward * 1e-3), "{:?} not outside", rec.p + outward * 1e-3);
+
+        for (d, du, dv) in [(rec.dpdu, DELTA, 0.0), (rec.dpdv, 0.0, DELTA)] {
+            // Skip the poles of the parametrization and steps off the edge of a part
+            if d.length() * DELTA > 1e-2 || d.length() == 0.0 || rec.v + dv > 1.0 {
+                continue;
+            }
+            assert!(Vec3::dot(&d, &outward).abs() <= 1e-3 * d.length(), "{:?} not tangent to {:?}", d, outward);
+            let q = rec.p + d * DELTA;
+            let probe = Ray::new(q + outward * 1e-2, -outward);
+            let moved = shape.hit(&probe, 0.0, 1.0).unwrap();
+            let error = (wrapped(moved.u, rec.u) - du).abs() + (wrapped(moved.v, rec.v) - dv).abs();
+            assert!(error <= 0.1 * DELTA, "uv moved by {} {} along {:?}", moved.u - rec.u, moved.v - rec.v, d);
+        }
+    }
+}
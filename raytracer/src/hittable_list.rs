@@ -1,5 +1,7 @@
+use crate::float::Float;
 use crate::hittable::{HitRecord, Hittable};
 use crate::ray::Ray;
+use crate::aabb::Aabb;
 
 pub struct HittableList {
     list: Vec<Box<dyn Hittable>>,
@@ -9,10 +11,14 @@ impl HittableList {
     pub fn new(list: Vec<Box<dyn Hittable>>) -> HittableList {
         HittableList { list }
     }
+
+    pub fn add(&mut self, object: Box<dyn Hittable>) {
+        self.list.push(object);
+    }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
         let mut hit_record = None;
         let mut closest_so_far = t_max;
 
@@ -24,4 +30,21 @@ impl Hittable for HittableList {
         }
         hit_record
     }
-}
\ No newline at end of file
+
+    fn transmittance(&self, r: &Ray, t_min: Float, t_max: Float) -> Float {
+        let mut transmittance = 1.0;
+        for object in &self.list {
+            transmittance *= object.transmittance(r, t_min, t_max);
+            if transmittance == 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.list.iter().map(|object| object.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |acc, b| Some(Aabb::surrounding(&acc, &b?)))
+    }
+}
@@ -0,0 +1,60 @@
+//! A small path tracer. Build a `Scene` out of `Hittable` objects with
+//! `Material`s, point a `Camera` at it and call `render` to get a
+//! `Framebuffer` back.
+
+pub mod float;
+pub mod vec3;
+pub mod ray;
+pub mod hittable;
+pub mod hittable_list;
+pub mod sphere;
+pub mod camera;
+pub mod material;
+pub mod microfacet;
+pub mod principled;
+pub mod ppm;
+pub mod plane;
+pub mod triangle;
+pub mod sampler;
+pub mod filter;
+pub mod framebuffer;
+pub mod render;
+pub mod scene;
+pub mod scenes;
+pub mod aabb;
+pub mod matrix;
+pub mod transform;
+pub mod quat;
+pub mod roots;
+pub mod cylinder;
+pub mod cone;
+pub mod torus;
+pub mod capsule;
+pub mod cube;
+pub mod csg;
+pub mod sdf;
+pub mod medium;
+pub mod noise;
+pub mod texture;
+pub mod normal_map;
+pub mod environment;
+pub mod sky;
+pub mod light;
+#[cfg(feature = "simd")]
+pub mod packet;
+
+pub use crate::aabb::Aabb;
+pub use crate::camera::Camera;
+pub use crate::filter::Filter;
+pub use crate::framebuffer::Framebuffer;
+pub use crate::hittable::{HitRecord, Hittable};
+pub use crate::hittable_list::HittableList;
+pub use crate::material::Material;
+pub use crate::matrix::{Mat3, Mat4};
+pub use crate::quat::Quat;
+pub use crate::ray::Ray;
+pub use crate::render::{render, CropWindow, RenderSettings, TileOrder};
+pub use crate::sampler::{Sampler, SamplerKind};
+pub use crate::scene::Scene;
+pub use crate::transform::Transform;
+pub use crate::vec3::{Color, Normal3, Point3, Vec3};
@@ -0,0 +1,146 @@
+//! Lights with no size, which only shadow rays can find.
+
+use crate::float::Float;
+use crate::vec3::Vec3;
+
+#[derive(Debug, Clone)]
+pub enum Light {
+    /// Shines `intensity` equally in every direction from `position`.
+    Point { position: Vec3, intensity: Vec3 },
+    /// Point light limited to a cone around `direction`, full strength
+    /// inside `cos_inner` and fading smoothly to nothing at `cos_outer`.
+    Spot { position: Vec3, direction: Vec3, intensity: Vec3, cos_inner: Float, cos_outer: Float },
+    /// Parallel light travelling along `direction` from infinitely far away,
+    /// giving `irradiance` on a surface facing it.
+    Directional { direction: Vec3, irradiance: Vec3 },
+}
+
+impl Light {
+    pub fn point(position: Vec3, intensity: Vec3) -> Light {
+        Light::Point { position, intensity }
+    }
+
+    /// Spot at `position` aimed at `target`, lighting a cone `angle` degrees
+    /// wide whose last `falloff` degrees fade out towards the edge.
+    pub fn spot(position: Vec3, target: Vec3, intensity: Vec3, angle: Float, falloff: Float) -> Light {
+        let outer = (angle / 2.0).to_radians();
+        let inner = (angle / 2.0 - falloff).max(0.0).to_radians();
+        Light::Spot {
+            position,
+            direction: Vec3::unit_vector(&(target - position)),
+            intensity,
+            cos_inner: inner.cos(),
+            cos_outer: outer.cos(),
+        }
+    }
+
+    pub fn directional(direction: Vec3, irradiance: Vec3) -> Light {
+        Light::Directional { direction: Vec3::unit_vector(&direction), irradiance }
+    }
+
+    /// Light arriving at `p`: the unit direction towards the light, the
+    /// radiance it brings as seen from a surface facing it, and how far a
+    /// shadow ray has to go to reach it. `None` at the position of a point
+    /// or spot light, where there is no direction to it.
+    pub fn illuminate(&self, p: Vec3) -> Option<(Vec3, Vec3, Float)> {
+        match *self {
+            Light::Point { position, intensity } => {
+                let (wi, distance) = towards(position, p)?;
+                Some((wi, intensity / (distance * distance), distance))
+            }
+            Light::Spot { position, direction, intensity, cos_inner, cos_outer } => {
+                let (wi, distance) = towards(position, p)?;
+                let falloff = smoothstep(cos_outer, cos_inner, Vec3::dot(&-wi, &direction));
+                Some((wi, intensity * (falloff / (distance * distance)), distance))
+            }
+            Light::Directional { direction, irradiance } => Some((-direction, irradiance, Float::MAX)),
+        }
+    }
+}
+
+/// Unit direction and distance from `p` to `position`, unless they coincide.
+fn towards(position: Vec3, p: Vec3) -> Option<(Vec3, Float)> {
+    let to_light = position - p;
+    let distance = to_light.length();
+    if distance > 0.0 { Some((to_light / distance, distance)) } else { None }
+}
+
+/// 0 below `low`, 1 above `high` and an S curve in between.
+fn smoothstep(low: Float, high: Float, x: Float) -> Float {
+    if low >= high {
+        return if x >= high { 1.0 } else { 0.0 };
+    }
+    let t = ((x - low) / (high - low)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-9;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-5;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < TOLERANCE * b.length().max(1.0), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn point_light_falls_off_with_the_square_of_the_distance() {
+        let intensity = Vec3::new(4.0, 8.0, 16.0);
+        let light = Light::point(Vec3::new(1.0, 2.0, 3.0), intensity);
+        for distance in [0.5, 1.0, 2.0, 10.0] {
+            let p = Vec3::new(1.0, 2.0 - distance, 3.0);
+            let (wi, radiance, reach) = light.illuminate(p).unwrap();
+            assert_close(wi, Vec3::new(0.0, 1.0, 0.0));
+            assert_close(radiance, intensity / (distance * distance));
+            assert!((reach - distance).abs() < TOLERANCE);
+        }
+        assert!(light.illuminate(Vec3::new(1.0, 2.0, 3.0)).is_none());
+    }
+
+    #[test]
+    fn spot_light_fades_between_inner_and_outer_cone() {
+        // Pointing down, full inside 20 degrees off the axis and dark past 30
+        let intensity = Vec3::new(1.0, 1.0, 1.0);
+        let light = Light::spot(Vec3::new(0.0, 5.0, 0.0), Vec3::default(), intensity, 60.0, 10.0);
+        // Seen from a unit distance, so only the falloff is left
+        let strength = |degrees: Float| {
+            let angle = degrees.to_radians();
+            light.illuminate(Vec3::new(angle.sin(), 5.0 - angle.cos(), 0.0)).unwrap().1.x()
+        };
+
+        for degrees in [0.0, 10.0, 19.9] {
+            assert!((strength(degrees) - 1.0).abs() < TOLERANCE, "{} degrees", degrees);
+        }
+        for degrees in [30.1, 45.0, 90.0, 180.0] {
+            assert_eq!(strength(degrees), 0.0, "{} degrees", degrees);
+        }
+        // Smoothstep in the cosine: halfway between the cosines is half as bright
+        let (cos_inner, cos_outer) = (Float::to_radians(20.0).cos(), Float::to_radians(30.0).cos());
+        let halfway = ((cos_inner + cos_outer) / 2.0).acos().to_degrees();
+        assert!((strength(halfway) - 0.5).abs() < 1e2 * TOLERANCE);
+        let fading: Vec<Float> = (0..=10).map(|i| strength(20.0 + i as Float)).collect();
+        assert!(fading.windows(2).all(|pair| pair[1] < pair[0]));
+
+        // Still the inverse square law along the axis
+        let (_, radiance, distance) = light.illuminate(Vec3::new(0.0, 3.0, 0.0)).unwrap();
+        assert!((distance - 2.0).abs() < TOLERANCE);
+        assert_close(radiance, intensity / 4.0);
+        assert!(light.illuminate(Vec3::new(0.0, 5.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn directional_light_ignores_distance() {
+        let irradiance = Vec3::new(3.0, 2.0, 1.0);
+        let light = Light::directional(Vec3::new(1.0, -1.0, 0.0), irradiance);
+        for p in [Vec3::default(), Vec3::new(1e3, -50.0, 7.0), Vec3::new(-1e-3, 1e4, 0.0)] {
+            let (wi, radiance, distance) = light.illuminate(p).unwrap();
+            assert_close(wi, Vec3::new(-1.0, 1.0, 0.0) / Float::sqrt(2.0));
+            assert_eq!(radiance, irradiance);
+            assert_eq!(distance, Float::MAX);
+        }
+    }
+}
@@ -1,350 +1,126 @@
-extern crate rand;
-extern crate rayon;
-extern crate indicatif;
-
-mod vec3;
-mod ray;
-mod hittable;
-mod hittable_list;
-mod sphere;
-mod camera;
-mod material;
-mod ppm;
-mod plane;
-
-use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write};
-
-use indicatif::{ProgressBar, ProgressStyle};
-use crate::ppm::gen_ppm;
-
-use image::{RgbImage, ImageBuffer, Rgb, imageops};
-use material::scatter;
-use rand::Rng;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use crate::ray::Ray;
-use crate::vec3::Vec3;
-use crate::hittable::{HitRecord, Hittable};
-use crate::hittable_list::*;
-use crate::sphere::Sphere;
-use crate::plane::{Plane, Rectangle, Aabb};
-use crate::camera::Camera;
-use crate::material::Material;
+use raytracer::ppm::gen_ppm;
+use raytracer::render::debug_pixel;
+use raytracer::float::Float;
+use raytracer::scenes;
+use raytracer::environment::{Environment, EnvironmentMap};
+use raytracer::sky::Sky;
+use raytracer::texture::Image;
+use raytracer::{render, CropWindow, Filter, RenderSettings, SamplerKind, TileOrder};
 
 fn main() {
 
     const IMAGE_WIDTH: u32 = 400;
     const IMAGE_HEIGHT: u32 = 300;
-    const SAMPLES: i32 = 8000;
-
-    
-
-    // let mut list: Vec<Box<dyn Hittable>> = Vec::new();
-    // list.push(Box::new(Sphere::sphere(
-    //     Vec3::new(0.0, 0.0, -1.0),
-    //      0.5,
-    //      Material::Lambertian { albedo: Vec3::random()})
-    //     ));
-
-    // list.push(Box::new(Sphere::sphere(
-    //     Vec3::new(-1.0, 0.0, -1.0),
-    //      0.5,
-    //      Material::Lambertian { albedo: Vec3::random()})
-    //     ));
-
-    // list.push(Box::new(Sphere::sphere(
-    //     Vec3::new(1.0, 0.0, -1.0),
-    //      0.5,
-    //      Material::Metal { albedo: Vec3::random(), fuzz: 0.2 })
-    //     ));
-
-
-    // list.push(Box::new(Sphere::sphere(
-    //     Vec3::new(0.0, -100.5, -1.0),
-    //     100.0,
-    //     Material::Metal { albedo: Vec3::random(), fuzz: 1.0 })
-    // ));
-
-
-    // let world = HittableList::new(list);
-
-    let world: HittableList = cornell_box();
-    
-    //Standard X-axis
-    let standard_cam = Camera::new(
-        Vec3::new(40.0, 20.0, 0.0),
-        Vec3::new(0.0, 20.0, 00.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        90.0,
-        IMAGE_WIDTH as f64 / IMAGE_HEIGHT as f64,
-    );
-    //Standard X-axis
-    let cam = Camera::new(
-        Vec3::new(40.0, 20.0, 0.0),
-        Vec3::new(0.0, 20.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        90.0,
-        IMAGE_WIDTH as f64 / IMAGE_HEIGHT as f64,
-    );
-
-
-    // let cam: Camera = Camera::new(Vec3::new(13.0, 2.0, 3.0), Vec3::new(0.0, 0.0, 0.0),
-    // Vec3::new(0.0, 1.0, 0.0), 20.0, (IMAGE_WIDTH as f64)/(IMAGE_HEIGHT as f64), 0.1, 10.0);
-
-    let bar = ProgressBar::new(IMAGE_HEIGHT.into());
-    bar.set_style(ProgressStyle::default_bar().template("[{elapsed} elapsed] {wide_bar:.cyan/white} {percent}% [{eta} remaining] [rendering]").ok().unwrap());
+    const SAMPLES: u32 = 8000;
 
     let filename = "data/new_image.png".to_string();
-
-    let scene: Vec<Vec<Vec3>> = (0..IMAGE_HEIGHT).into_par_iter().map(|y_rev| {
-        let y: f64 = IMAGE_HEIGHT as f64 - y_rev as f64 - 1.0;
-        let row: Vec<Vec3> = (0..IMAGE_WIDTH).into_par_iter().map(|x| {
-            let mut color_vector: Vec3 = Vec3::new(0.0, 0.0, 0.0);
-            for _s in 0..SAMPLES {
-                let u: f64 = (x as f64 + rand::random::<f64>()) / IMAGE_WIDTH as f64;
-                let v: f64 = (y as f64 + rand::random::<f64>()) / IMAGE_HEIGHT as f64;
-                let r: Ray = cam.get_ray(u, v);
-                color_vector = color_vector + color(&r, &world, 10);
+    // --scene picks cornell, random, plane, shapes, csg, sdf, clouds, materials, detail, daylight or studio,
+    // --environment lights it with an equirectangular (HDR) image instead,
+    // --sky elevation,azimuth[,turbidity] lights it with daylight from a sun at those angles,
+    // --samples overrides the sample count,
+    // --sampler picks random, stratified, halton, sobol or bluenoise,
+    // --filter picks box, tent, gaussian, mitchell or lanczos,
+    // --tile-order picks scanline, spiral or hilbert and
+    // --crop x0,y0,x1,y1 only renders and writes that pixel rectangle and
+    // --debug-pixel x,y traces a single pixel with every bounce logged
+    let args: Vec<String> = std::env::args().collect();
+    let scene_name = arg_value(&args, "--scene").unwrap_or("cornell");
+    let Some((mut scene, cam)) = scenes::by_name(scene_name, IMAGE_WIDTH as Float / IMAGE_HEIGHT as Float) else {
+        eprintln!("unknown scene {}", scene_name);
+        std::process::exit(1);
+    };
+    if let Some(path) = arg_value(&args, "--environment") {
+        match Image::open(path) {
+            Ok(image) => scene = scene.with_environment(Environment::Map(EnvironmentMap::new(image).into())),
+            Err(err) => {
+                eprintln!("cannot load environment {}: {}", path, err);
+                std::process::exit(1);
             }
-            color_vector = color_vector/SAMPLES as f64;
-            color_vector = 255.99*Vec3::new(color_vector.x().sqrt(), color_vector.y().sqrt(), color_vector.z().sqrt());
-            color_vector.colorize();
-            color_vector
-        }).collect();
-        bar.inc(1);
-        row
-    }).collect();
-
-    bar.finish();
-
-    gen_ppm(scene, filename);
-}
-
-fn color(r: &Ray, world: &HittableList, depth: i64) -> Vec3 {
-    if let Some(rec) = world.hit(&r, 0.001, std::f64::MAX) {
-        let mut scattered = Ray::ray(Vec3::default(), Vec3::default());
-        let mut attentuation = Vec3::default();
-
-        if depth < 50 && scatter(&rec.material, r, &rec, &mut attentuation, &mut scattered) {
-            return attentuation * color(&scattered, world, depth+1);
-        }
-        else {
-            return Vec3::new(0.0, 0.0, 0.0);
         }
     }
-    else {
-        let unit_direction = Vec3::unit_vector(&r.direction());
-        let t = 0.5 * (unit_direction.y() + 1.0);
-        Vec3::new(1.0, 1.0, 1.0) * (1.0 - t) + Vec3::new(0.5, 0.7, 1.0) * t
+    if let Some(value) = arg_value(&args, "--sky") {
+        let Some(sky) = parse_sky(value) else {
+            eprintln!("invalid sky {}, expected elevation,azimuth[,turbidity]", value);
+            std::process::exit(1);
+        };
+        scene = scene.with_environment(Environment::Sky(sky.into()));
     }
-}
-
-pub fn random_in_unit_sphere() -> Vec3 {
-    let mut p = Vec3::default();
-    let mut rng_double = rand::thread_rng();
-
-    loop {
-        p = 2.0 * Vec3::new(rng_double.gen::<f64>(), rng_double.gen::<f64>(), rng_double.gen::<f64>()) - Vec3::new(1.0, 1.0, 1.0);
-        if p.squared_length() < 1.0 {
-            return p;
-        }
+    let settings = RenderSettings {
+        width: IMAGE_WIDTH,
+        height: IMAGE_HEIGHT,
+        samples: parsed_arg(&args, "--samples", "a positive sample count", |s| s.parse().ok().filter(|&samples| samples > 0))
+            .unwrap_or(SAMPLES),
+        sampler: parsed_arg(&args, "--sampler", "random, stratified, halton, sobol or bluenoise", SamplerKind::from_name)
+            .unwrap_or(SamplerKind::Sobol),
+        filter: parsed_arg(&args, "--filter", "box, tent, gaussian, mitchell or lanczos", Filter::from_name)
+            .unwrap_or_default(),
+        tile_size: parsed_arg(&args, "--tile-size", "a positive number of pixels", |s| s.parse().ok().filter(|&size| size > 0))
+            .unwrap_or(32),
+        tile_order: parsed_arg(&args, "--tile-order", "scanline, spiral or hilbert", TileOrder::from_name)
+            .unwrap_or(TileOrder::Spiral),
+        crop: parsed_arg(
+            &args,
+            "--crop",
+            &format!("x0,y0,x1,y1 with x0 < x1 <= {} and y0 < y1 <= {}", IMAGE_WIDTH, IMAGE_HEIGHT),
+            |s| parse_crop(s, IMAGE_WIDTH, IMAGE_HEIGHT),
+        ),
+    };
+
+    let debug_pixel_expected = format!("x,y with x < {} and y < {}", IMAGE_WIDTH, IMAGE_HEIGHT);
+    if let Some((x, y)) = parsed_arg(&args, "--debug-pixel", &debug_pixel_expected, |s| parse_pixel(s, IMAGE_WIDTH, IMAGE_HEIGHT)) {
+        debug_pixel(&scene, &cam, &settings, x, y);
+        return;
     }
-}
 
-fn random_scene() -> HittableList {
-    let mut rng = rand::thread_rng();
-
-    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
-
-    list.push(Box::new(Sphere::sphere(
-        Vec3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        Material::Lambertian { albedo: Vec3::new(0.5, 0.5, 0.5) })
-    ));
+    let mut framebuffer = render(&scene, &cam, &settings);
+    if let Some(crop) = settings.crop {
+        framebuffer = framebuffer.crop(crop.x0, crop.y0, crop.x1, crop.y1);
+    }
 
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat: f64 = rng.gen::<f64>();
-            let center: Vec3 = Vec3::new(a as f64 + 0.9 * rng.gen::<f64>(),
-                0.2, b as f64 + 0.9 * rng.gen::<f64>());
-            if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                if choose_mat < 0.8 {  // diffuse
-                    list.push(Box::new(Sphere::sphere(
-                        center,
-                        0.2,
-                        Material::Lambertian { albedo: Vec3::random() })
-                    ));
-                } else {  //metal
-                    list.push(Box::new(Sphere::sphere(
-                        center,
-                        0.2,
-                        Material::Metal {
-                            albedo: Vec3::new(
-                                0.5 * (1.0 + rng.gen::<f64>()),
-                                0.5 * (1.0 + rng.gen::<f64>()),
-                                0.5 * (1.0 + rng.gen::<f64>())),
-                            fuzz: 0.5 * rng.gen::<f64>() })
-                    ));
-                }
-            }
+    gen_ppm(framebuffer.to_rows(), filename);
+}
 
-            list.push(Box::new(Sphere::sphere(
-                Vec3::new(-4.0, 1.0, 0.0),
-                1.0,
-                Material::Lambertian { albedo: Vec3::new(0.4, 0.4, 0.1) })
-            ));
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).map(|s| s.as_str())
+}
 
-            list.push(Box::new(Sphere::sphere(
-                Vec3::new(4.0, 1.0, 0.0),
-                1.0,
-                Material::Metal { albedo: Vec3::new(0.7, 0.6, 0.5), fuzz: 0.0 })
-            ));
-        }
+/// Parses the value after `flag`, or exits with a message saying what was
+/// `expected` if it is missing or does not parse.
+fn parsed_arg<T>(args: &[String], flag: &str, expected: &str, parse: impl FnOnce(&str) -> Option<T>) -> Option<T> {
+    if !args.iter().any(|a| a == flag) {
+        return None;
     }
-    let world = HittableList::new(list);
-    world
+    let value = arg_value(args, flag).unwrap_or("");
+    let Some(parsed) = parse(value) else {
+        eprintln!("invalid {} {}, expected {}", flag.trim_start_matches('-'), value, expected);
+        std::process::exit(1);
+    };
+    Some(parsed)
 }
 
-fn plane_scene() -> HittableList {
-    let mut rng = rand::thread_rng();
-
-    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
-
-    // list.push(Box::new(Sphere::sphere(
-    //     Vec3::new(0.0, -1000.0, 0.0),
-    //     1000.0,
-    //     Material::Lambertian { albedo: Vec3::new(0.5, 0.5, 0.5) })
-    // ));
-
-            list.push(Box::new(Sphere::sphere(
-                Vec3::new(0.0, 0.0, -1.0),
-                0.5,
-                Material::Lambertian { albedo: Vec3::new(0.8, 0.8, 0.3) })
-            ));
-
-            list.push(Box::new(Sphere::sphere(
-                Vec3::new(0.0, -100.5, -1.0),
-                100.0,
-                Material::Lambertian { albedo: Vec3::new(0.8, 0.8, 0.0) })
-            ));
-
-
-            list.push(Box::new(Plane::plane(
-                Vec3::new(0.0, 1.0, 0.0),
-                2.0,
-                Material::Lambertian { albedo: Vec3::new(0.8, 0.8, 0.0) })
-            ));
-            let world = HittableList::new(list);
-            world
+/// A pixel inside a `width` by `height` image.
+fn parse_pixel(value: &str, width: u32, height: u32) -> Option<(u32, u32)> {
+    let (x, y) = value.split_once(',')?;
+    let (x, y) = (x.trim().parse().ok()?, y.trim().parse().ok()?);
+    (x < width && y < height).then_some((x, y))
 }
 
-fn cornell_box() -> HittableList {
-    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
-
-    // Set up the objects in the scene
-    let red = Material::Lambertian { albedo: Vec3::new(0.65, 0.05, 0.05) };
-    let white = Material::Lambertian { albedo: Vec3::new(0.73, 0.73, 0.73) };
-    let green = Material::Lambertian { albedo:Vec3::new(0.12, 0.45, 0.15) };
-    let light = Material::Light { emittance: Vec3::new(40.0, 20.0, 10.0) };
-
-    // Add the floor
-    list.push(Box::new(Plane::plane(
-        Vec3::new(0.0, 1.0, 0.0),
-        0.0,
-        white,
-    )));
-
-    // Add the roof
-    list.push(Box::new(Plane::plane(
-        Vec3::new(0.0, 1.0, 0.0),
-        40.0,
-        white,
-    )));
-
-    // Add the backwall
-    list.push(Box::new(Plane::plane(
-        Vec3::new(1.0, 0.0, 0.0),
-        0.0,
-        white,
-    )));
-    // Add the leftwall
-    list.push(Box::new(Plane::plane(
-        Vec3::new(0.0, 0.0, 1.0),
-        20.0,
-        white,
-    )));
-
-    // Add the rightwall
-    list.push(Box::new(Plane::plane(
-        Vec3::new(0.0, 0.0, 1.0),
-        -20.0,
-        white,
-    )));
-
-    //center sphere
-    list.push(Box::new(Sphere::sphere(
-        Vec3::new(10.0, 10.0, 0.0),
-        5.0,
-        red
-    )));
-    //light source sphere
-    // list.push(Box::new(Sphere::sphere(
-    //     Vec3::new(20.0, 40.0, 20.0),
-    //     5.0,
-    //     light
-    // )));
-
-    // list.push(Box::new(Plane::plane(
-    //     Vec3::new(1.0, 0.0, 0.0),
-    //     1.0,
-    //     green
-    // )));
-
-    // list.push(Box::new(Plane::plane(
-    //     Vec3::new(0.0, 1.0, 0.0),
-    //     1.0,
-    //     white
-    // )));
-
-    // // Add the floor
-    // let floor = Rectangle {
-    //     p0: Vec3::new(-100.0, 0.0, -100.0),
-    //     p1: Vec3::new(-100.0, 0.0, 100.0),
-    //     p2: Vec3::new(100.0, 0.0, 100.0),
-    //     material: white.clone(),
-    //     aabb: Aabb::new(Vec3::new(-100.0, 0.0, -100.0), Vec3::new(100.0, 0.0, 100.0)),
-    // };
-    // list.push(Box::new(floor));
-
-    // // Add the ceiling
-    // let ceiling = Rectangle {
-    //     p0: Vec3::new(-100.0, 100.0, 100.0),
-    //     p1: Vec3::new(-100.0, 100.0, -100.0),
-    //     p2: Vec3::new(100.0, 100.0, -100.0),
-    //     material: white.clone(),
-    //     aabb: Aabb::new(Vec3::new(-100.0, 100.0, -100.0), Vec3::new(100.0, 100.0, 100.0)),
-    // };
-    // list.push(Box::new(ceiling));
-
-    // // Add the walls
-    // let left_wall = Rectangle {
-    //     p0: Vec3::new(-100.0, 0.0, 100.0),
-    //     p1: Vec3::new(-100.0, 0.0, -100.0),
-    //     p2: Vec3::new(-100.0, 100.0, -100.0),
-    //     material: green.clone(),
-    //     aabb: Aabb::new(Vec3::new(-100.0, 0.0, -100.0), Vec3::new(-100.0, 100.0, 100.0)),
-    // };
-    // list.push(Box::new(left_wall));
-
-    // let right_wall = Rectangle {
-    //     p0: Vec3::new(100.0, 0.0, -100.0),
-    //     p1: Vec3::new(100.0, 0.0, 100.0),
-    //     p2: Vec3::new(100.0, 100.0, 100.0),
-    //     material: red.clone(),
-    //     aabb: Aabb::new(Vec3::new(100.0, 0.0, 100.0), Vec3::new(100.0, 100.0, 100.0)),
-    // };
-    // list.push(Box::new(right_wall));
+fn parse_sky(value: &str) -> Option<Sky> {
+    let angles: Vec<Float> = value.split(',').map(|v| v.trim().parse().ok()).collect::<Option<_>>()?;
+    // Without a turbidity, a clear day
+    let (elevation, azimuth, turbidity) = match angles[..] {
+        [elevation, azimuth] => (elevation, azimuth, 3.0),
+        [elevation, azimuth, turbidity] => (elevation, azimuth, turbidity),
+        _ => return None,
+    };
+    Some(Sky::new(Sky::sun_direction(elevation, azimuth), turbidity))
+}
 
-    let world = HittableList::new(list);
-    world
+/// A non-empty window inside a `width` by `height` image.
+fn parse_crop(value: &str, width: u32, height: u32) -> Option<CropWindow> {
+    let bounds: Vec<u32> = value.split(',').map(|v| v.trim().parse().ok()).collect::<Option<_>>()?;
+    match bounds[..] {
+        [x0, y0, x1, y1] if x0 < x1 && x1 <= width && y0 < y1 && y1 <= height => Some(CropWindow::new(x0, y0, x1, y1)),
+        _ => None,
+    }
 }
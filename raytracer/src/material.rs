@@ -1,16 +1,80 @@
-use crate::ray::Ray;
+use crate::float::Float;
+use crate::float::consts::PI;
+use crate::matrix::Mat3;
 use crate::vec3::Vec3;
 use crate::hittable::HitRecord;
-use crate::random_in_unit_sphere;
+use crate::sampler::{Sampler, sample_cosine_hemisphere, sample_unit_ball, sample_unit_sphere};
+use crate::microfacet::{self, Ggx};
+use crate::principled::Principled;
+use crate::texture::Texture;
 
+use std::sync::Arc;
 
 
-#[derive(Debug, Clone, Copy)]
+
+#[derive(Debug, Clone)]
 pub enum Material {
     Lambertian { albedo: Vec3 },
-    Metal {albedo: Vec3, fuzz: f64 },
-    Dialectric {},
+    Metal {albedo: Vec3, fuzz: Float },
+    /// Rough metal with a GGX microfacet distribution. `eta` and `k` are the
+    /// real and imaginary parts of the index of refraction per channel;
+    /// `roughness` goes from a mirror at 0 to fully rough at 1.
+    Conductor { eta: Vec3, k: Vec3, roughness: Float },
+    /// Glass-like boundary with index of refraction `ior` on the inside,
+    /// reflecting and refracting through GGX microfacets.
+    Dielectric { ior: Float, roughness: Float },
+    /// Layered glTF style material covering most opaque and glassy looks.
+    Principled(Principled),
     Light {emittance: Vec3 },
+    /// Scatters equally in all directions, for the inside of volumes.
+    Isotropic { albedo: Vec3 },
+    /// Henyey-Greenstein phase function for volumes. `g` in `(-1, 1)` is the
+    /// mean cosine of the scattering angle: positive scatters forwards,
+    /// negative backwards and zero is isotropic.
+    HenyeyGreenstein { albedo: Vec3, g: Float },
+    /// Blend of `a` and `b`, with `mask` giving the amount of `b` at each
+    /// point.
+    Mix { a: Arc<Material>, b: Arc<Material>, mask: Texture },
+    /// `base` under a clear dielectric layer with index of refraction `ior`,
+    /// like varnish or the clear coat of car paint. Light reaching the base
+    /// is what the layer does not reflect, on the way in and out.
+    Coated { base: Arc<Material>, ior: Float, roughness: Float },
+}
+
+impl Material {
+    pub fn gold(roughness: Float) -> Material {
+        Material::Conductor {
+            eta: Vec3::new(0.143, 0.374, 1.442),
+            k: Vec3::new(3.983, 2.385, 1.603),
+            roughness,
+        }
+    }
+
+    pub fn copper(roughness: Float) -> Material {
+        Material::Conductor {
+            eta: Vec3::new(0.200, 0.924, 1.102),
+            k: Vec3::new(3.912, 2.452, 2.142),
+            roughness,
+        }
+    }
+
+    pub fn aluminium(roughness: Float) -> Material {
+        Material::Conductor {
+            eta: Vec3::new(1.657, 0.880, 0.521),
+            k: Vec3::new(9.224, 6.270, 4.837),
+            roughness,
+        }
+    }
+}
+
+impl Material {
+    pub fn mix(a: Material, b: Material, mask: Texture) -> Material {
+        Material::Mix { a: Arc::new(a), b: Arc::new(b), mask }
+    }
+
+    pub fn coated(base: Material, ior: Float, roughness: Float) -> Material {
+        Material::Coated { base: Arc::new(base), ior, roughness }
+    }
 }
 
 impl Default for Material {
@@ -19,30 +83,303 @@ impl Default for Material {
     }
 }
 
-pub fn scatter(material: &Material, ray_in: &Ray, rec: &HitRecord, attentuation: &mut Vec3, scattered: &mut Ray) -> bool {
-    match material {
-        &Material::Lambertian { albedo } => {
-            let target = rec.p + rec.normal + random_in_unit_sphere();
-            *scattered = Ray::ray(rec.p, target - rec.p);
-            *attentuation = albedo;
-            return true;
+/// Direction picked by `Material::sample`, with the value of the BSDF
+/// times the cosine at `wi` and the density it was picked with, so the
+/// path throughput is multiplied by `f / pdf`.
+#[derive(Debug, Clone, Copy)]
+pub struct BsdfSample {
+    pub wi: Vec3,
+    pub f: Vec3,
+    pub pdf: Float,
+    /// Picked from a delta lobe, which `eval` and `pdf` never see. `pdf` is
+    /// then 1 and `f` is the whole weight.
+    pub specular: bool,
+}
+
+impl Material {
+    /// True when the material only scatters into delta lobes, so there is no
+    /// point in sampling lights from it.
+    pub fn is_specular(&self) -> bool {
+        match *self {
+            Material::Metal { .. } => true,
+            Material::Conductor { roughness, .. } | Material::Dielectric { roughness, .. } => {
+                Ggx::from_roughness(roughness).is_smooth()
+            }
+            Material::Mix { ref a, ref b, .. } => a.is_specular() && b.is_specular(),
+            Material::Coated { ref base, roughness, .. } => Ggx::from_roughness(roughness).is_smooth() && base.is_specular(),
+            _ => false,
+        }
+    }
+
+    /// Scattered fraction of the light arriving along `wi` and leaving along
+    /// `wo`, times the cosine at `wi`. Both point away from the hit. Delta
+    /// lobes contribute nothing.
+    pub fn eval(&self, wo: Vec3, wi: Vec3, rec: &HitRecord) -> Vec3 {
+        if self.is_specular() {
+            return Vec3::default();
+        }
+        self.eval_lobes(wo, wi, rec)
+    }
+
+    /// Density per unit solid angle of `sample` returning `wi`, leaving out
+    /// delta lobes.
+    pub fn pdf(&self, wo: Vec3, wi: Vec3, rec: &HitRecord) -> Float {
+        if self.is_specular() {
+            return 0.0;
         }
-        &Material::Metal { albedo, fuzz } => {
-            let mut f = 1.0;
-            if fuzz < 1.0 {
-                f = fuzz;
+        self.pdf_lobes(wo, wi, rec)
+    }
+
+    /// `eval` without the check for delta lobes, which are still smooth
+    /// enough to evaluate when they come from a very narrow GGX lobe.
+    fn eval_lobes(&self, wo: Vec3, wi: Vec3, rec: &HitRecord) -> Vec3 {
+        let frame = Mat3::onb(rec.shading_normal.into());
+        let (wo_local, wi_local) = (frame.transpose() * wo, frame.transpose() * wi);
+        match *self {
+            Material::Lambertian { albedo } => albedo * (wi_local.z().max(0.0) / PI),
+            Material::Conductor { eta, k, roughness } => {
+                let Some(h) = microfacet::reflection_half_vector(wo_local, wi_local) else {
+                    return Vec3::default();
+                };
+                let ggx = Ggx::from_roughness(roughness);
+                microfacet::fresnel_conductor(Vec3::dot(&wo_local, &h), eta, k) * (ggx.reflection(wo_local, wi_local, h) * wi_local.z())
+            }
+            Material::Dielectric { ior, roughness } => {
+                let value = dielectric_eval(wo_local, wi_local, relative_ior(ior, rec), roughness);
+                Vec3::new(value, value, value)
+            }
+            Material::Principled(principled) => principled.eval(wo_local, wi_local, rec.front_face) * wi_local.z().abs(),
+            Material::Isotropic { albedo } => albedo / (4.0 * PI),
+            Material::HenyeyGreenstein { albedo, g } => albedo * henyey_greenstein(Vec3::dot(&-wo, &wi), g),
+            Material::Mix { ref a, ref b, ref mask } => {
+                let t = mask.scalar(rec.u, rec.v, rec.p).clamp(0.0, 1.0);
+                a.eval(wo, wi, rec) * (1.0 - t) + b.eval(wo, wi, rec) * t
+            }
+            Material::Coated { ref base, ior, roughness } => {
+                if !rec.front_face {
+                    return base.eval(wo, wi, rec);
+                }
+                let ggx = Ggx::from_roughness(roughness);
+                let coat = match microfacet::reflection_half_vector(wo_local, wi_local) {
+                    Some(h) if !ggx.is_smooth() => {
+                        microfacet::fresnel_dielectric(Vec3::dot(&wo_local, &h), ior) * ggx.reflection(wo_local, wi_local, h) * wi_local.z()
+                    }
+                    _ => 0.0,
+                };
+                base.eval(wo, wi, rec) * coat_transmittance(wo_local, wi_local, ior) + Vec3::new(coat, coat, coat)
             }
-            let reflected = reflect(Vec3::unit_vector(&ray_in.direction()), rec.normal);
-            *scattered = Ray::ray(rec.p, reflected + fuzz * random_in_unit_sphere());
-            *attentuation = albedo;
-            return Vec3::dot(&scattered.direction(), &rec.normal) > 0.0;
+            Material::Metal { .. } | Material::Light { .. } => Vec3::default(),
         }
-        &Material::Dialectric {  } => { return false; }
-        &Material::Light { emittance } => {
-            *scattered = Ray::ray(rec.p, rec.normal);
-            *attentuation = emittance;
-            return true
+    }
+
+    fn pdf_lobes(&self, wo: Vec3, wi: Vec3, rec: &HitRecord) -> Float {
+        let frame = Mat3::onb(rec.shading_normal.into());
+        let (wo_local, wi_local) = (frame.transpose() * wo, frame.transpose() * wi);
+        match *self {
+            Material::Lambertian { .. } => wi_local.z().max(0.0) / PI,
+            Material::Conductor { roughness, .. } => match microfacet::reflection_half_vector(wo_local, wi_local) {
+                Some(h) => Ggx::from_roughness(roughness).reflection_pdf(wo_local, h),
+                None => 0.0,
+            },
+            Material::Dielectric { ior, roughness } => dielectric_pdf(wo_local, wi_local, relative_ior(ior, rec), roughness),
+            Material::Principled(principled) => principled.pdf(wo_local, wi_local, rec.front_face),
+            Material::Isotropic { .. } => 1.0 / (4.0 * PI),
+            Material::HenyeyGreenstein { g, .. } => henyey_greenstein(Vec3::dot(&-wo, &wi), g),
+            Material::Mix { ref a, ref b, ref mask } => {
+                let t = mask.scalar(rec.u, rec.v, rec.p).clamp(0.0, 1.0);
+                a.pdf(wo, wi, rec) * (1.0 - t) + b.pdf(wo, wi, rec) * t
+            }
+            Material::Coated { ref base, ior, roughness } => {
+                if !rec.front_face {
+                    return base.pdf(wo, wi, rec);
+                }
+                let ggx = Ggx::from_roughness(roughness);
+                let p_coat = microfacet::fresnel_dielectric(wo_local.z(), ior);
+                let coat = match microfacet::reflection_half_vector(wo_local, wi_local) {
+                    Some(h) if !ggx.is_smooth() => ggx.reflection_pdf(wo_local, h),
+                    _ => 0.0,
+                };
+                p_coat * coat + (1.0 - p_coat) * base.pdf(wo, wi, rec)
+            }
+            Material::Metal { .. } | Material::Light { .. } => 0.0,
+        }
+    }
+
+    /// Picks a direction for the light leaving along `wo` to have come
+    /// from. `None` when the path ends here.
+    pub fn sample(&self, wo: Vec3, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<BsdfSample> {
+        let frame = Mat3::onb(rec.shading_normal.into());
+        let wo_local = frame.transpose() * wo;
+        let (wi, f, pdf) = match *self {
+            Material::Lambertian { albedo } => {
+                let wi = sample_cosine_hemisphere(sampler.get_2d());
+                (frame * wi, albedo * (wi.z() / PI), wi.z() / PI)
+            }
+            Material::Metal { albedo, fuzz } => {
+                let u = sampler.get_2d();
+                let wi = reflect(-wo, rec.shading_normal.into()) + fuzz.min(1.0) * sample_unit_ball(u, sampler.get_1d());
+                if Vec3::dot(&wi, &rec.shading_normal.into()) <= 0.0 {
+                    return None;
+                }
+                (Vec3::unit_vector(&wi), albedo, 1.0)
+            }
+            Material::Conductor { roughness, .. } => {
+                let m = Ggx::from_roughness(roughness).sample_visible_normal(wo_local, sampler.get_2d());
+                let wi = frame * microfacet::reflect(wo_local, m);
+                (wi, self.eval_lobes(wo, wi, rec), self.pdf_lobes(wo, wi, rec))
+            }
+            Material::Dielectric { ior, roughness } => {
+                // Reflect or refract with the probability of the Fresnel term
+                let eta = relative_ior(ior, rec);
+                let m = Ggx::from_roughness(roughness).sample_visible_normal(wo_local, sampler.get_2d());
+                let fresnel = microfacet::fresnel_dielectric(Vec3::dot(&wo_local, &m), eta);
+                let wi = match microfacet::refract(wo_local, m, eta) {
+                    Some(wt) if sampler.get_1d() >= fresnel => wt,
+                    _ => microfacet::reflect(wo_local, m),
+                };
+                let wi = frame * wi;
+                (wi, self.eval_lobes(wo, wi, rec), self.pdf_lobes(wo, wi, rec))
+            }
+            Material::Principled(principled) => {
+                let u_lobe = sampler.get_1d();
+                let (wi, f, pdf) = principled.sample(wo_local, rec.front_face, u_lobe, sampler.get_2d())?;
+                (frame * wi, f * wi.z().abs(), pdf)
+            }
+            Material::Light { .. } => return None,
+            Material::Isotropic { albedo } => {
+                (sample_unit_sphere(sampler.get_2d()), albedo / (4.0 * PI), 1.0 / (4.0 * PI))
+            }
+            Material::HenyeyGreenstein { albedo, g } => {
+                // Sampled exactly, so the weight is just the albedo
+                let wi = sample_henyey_greenstein(-wo, g, sampler.get_2d());
+                let pdf = henyey_greenstein(Vec3::dot(&-wo, &wi), g);
+                (wi, albedo * pdf, pdf)
+            }
+            Material::Mix { ref a, ref b, ref mask } => {
+                // Picking each side with its share of the blend leaves delta
+                // samples with their own weight
+                let t = mask.scalar(rec.u, rec.v, rec.p).clamp(0.0, 1.0);
+                let chosen = if sampler.get_1d() < t { b } else { a };
+                let sample = chosen.sample(wo, rec, sampler)?;
+                if sample.specular {
+                    return Some(sample);
+                }
+                (sample.wi, self.eval_lobes(wo, sample.wi, rec), self.pdf_lobes(wo, sample.wi, rec))
+            }
+            Material::Coated { ref base, ior, roughness } => {
+                if !rec.front_face {
+                    return base.sample(wo, rec, sampler);
+                }
+                // Sample the coat as often as it reflects
+                let p_coat = microfacet::fresnel_dielectric(wo_local.z(), ior);
+                let wi = if sampler.get_1d() < p_coat {
+                    let ggx = Ggx::from_roughness(roughness);
+                    let m = ggx.sample_visible_normal(wo_local, sampler.get_2d());
+                    let wi = microfacet::reflect(wo_local, m);
+                    if wi.z() <= 0.0 {
+                        return None;
+                    }
+                    if ggx.is_smooth() {
+                        let weight = microfacet::fresnel_dielectric(Vec3::dot(&wo_local, &m), ior) * ggx.g(wo_local, wi) / (ggx.g1(wo_local) * p_coat);
+                        return Some(BsdfSample { wi: frame * wi, f: Vec3::new(weight, weight, weight), pdf: 1.0, specular: true });
+                    }
+                    frame * wi
+                } else {
+                    let sample = base.sample(wo, rec, sampler)?;
+                    if sample.specular {
+                        let scale = coat_transmittance(wo_local, frame.transpose() * sample.wi, ior) / (1.0 - p_coat);
+                        return Some(BsdfSample { f: sample.f * scale, ..sample });
+                    }
+                    sample.wi
+                };
+                (wi, self.eval_lobes(wo, wi, rec), self.pdf_lobes(wo, wi, rec))
+            }
+        };
+
+        if pdf <= 0.0 || pdf.is_nan() {
+            return None;
+        }
+        let specular = self.is_specular();
+        if specular {
+            // Near-delta lobes keep their sampled weight but drop the density
+            return Some(BsdfSample { wi, f: f / pdf, pdf: 1.0, specular });
+        }
+        Some(BsdfSample { wi, f, pdf, specular })
+    }
+}
+
+/// Fraction of the light getting through a clear coat on the way in along
+/// `wi` and out along `wo`.
+fn coat_transmittance(wo: Vec3, wi: Vec3, ior: Float) -> Float {
+    (1.0 - microfacet::fresnel_dielectric(wo.z().abs(), ior)) * (1.0 - microfacet::fresnel_dielectric(wi.z().abs(), ior))
+}
+
+/// Index of refraction across the boundary for light arriving from the
+/// side the normal faces, for a material with `ior` on the inside.
+fn relative_ior(ior: Float, rec: &HitRecord) -> Float {
+    if rec.front_face { ior } else { 1.0 / ior }
+}
+
+/// Rough dielectric reflection and transmission in the local frame, times
+/// the cosine at `wi`.
+fn dielectric_eval(wo: Vec3, wi: Vec3, eta: Float, roughness: Float) -> Float {
+    let ggx = Ggx::from_roughness(roughness);
+    if let Some(h) = microfacet::reflection_half_vector(wo, wi) {
+        let fresnel = microfacet::fresnel_dielectric(Vec3::dot(&wo, &h), eta);
+        return fresnel * ggx.reflection(wo, wi, h) * wi.z();
+    }
+    match microfacet::refraction_half_vector(wo, wi, eta) {
+        Some(h) => {
+            let fresnel = microfacet::fresnel_dielectric(Vec3::dot(&wo, &h), eta);
+            (1.0 - fresnel) * ggx.transmission(wo, wi, h, eta) * wi.z().abs()
+        }
+        None => 0.0,
+    }
+}
+
+fn dielectric_pdf(wo: Vec3, wi: Vec3, eta: Float, roughness: Float) -> Float {
+    let ggx = Ggx::from_roughness(roughness);
+    if let Some(h) = microfacet::reflection_half_vector(wo, wi) {
+        return microfacet::fresnel_dielectric(Vec3::dot(&wo, &h), eta) * ggx.reflection_pdf(wo, h);
+    }
+    match microfacet::refraction_half_vector(wo, wi, eta) {
+        Some(h) => (1.0 - microfacet::fresnel_dielectric(Vec3::dot(&wo, &h), eta)) * ggx.transmission_pdf(wo, wi, h, eta),
+        None => 0.0,
+    }
+}
+
+/// Henyey-Greenstein phase function for scattering by an angle with cosine `cos_theta`.
+fn henyey_greenstein(cos_theta: Float, g: Float) -> Float {
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * PI * denom * denom.max(0.0).sqrt())
+}
+
+/// Direction scattered from one travelling along `d`, distributed by the
+/// Henyey-Greenstein phase function. Sampled exactly, so the weight is one.
+fn sample_henyey_greenstein(d: Vec3, g: Float, u: (Float, Float)) -> Vec3 {
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * u.0
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u.0);
+        (1.0 + g * g - s * s) / (2.0 * g)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Mat3::onb(d) * Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// Radiance given off at the hit. Lights shine from both faces, principled
+/// emission only from the front face.
+pub fn emitted(material: &Material, rec: &HitRecord) -> Vec3 {
+    match *material {
+        Material::Light { emittance } => emittance,
+        Material::Principled(principled) if rec.front_face => principled.emission,
+        Material::Mix { ref a, ref b, ref mask } => {
+            let t = mask.scalar(rec.u, rec.v, rec.p).clamp(0.0, 1.0);
+            emitted(a, rec) * (1.0 - t) + emitted(b, rec) * t
         }
+        Material::Coated { ref base, .. } => emitted(base, rec),
+        _ => Vec3::default(),
     }
 }
 
@@ -0,0 +1,422 @@
+use std::ops;
+
+use crate::float::{Float, gamma};
+use crate::vec3::{Normal3, Point3, Vec3};
+
+/// Row-major 3x3 matrix for linear maps and orthonormal bases.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat3 {
+    pub m: [[Float; 3]; 3],
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Mat3::identity()
+    }
+}
+
+impl Mat3 {
+    pub fn new(m: [[Float; 3]; 3]) -> Mat3 {
+        Mat3 { m }
+    }
+
+    pub fn identity() -> Mat3 {
+        Mat3::new([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    /// Matrix whose columns are `a`, `b` and `c`.
+    pub fn from_cols(a: Vec3, b: Vec3, c: Vec3) -> Mat3 {
+        Mat3::new([
+            [a.x(), b.x(), c.x()],
+            [a.y(), b.y(), c.y()],
+            [a.z(), b.z(), c.z()],
+        ])
+    }
+
+    /// Orthonormal basis with `n` as its third column (Duff et al.,
+    /// "Building an Orthonormal Basis, Revisited"). Multiplying a local
+    /// direction with z up gives the world direction around `n`.
+    pub fn onb(n: Vec3) -> Mat3 {
+        let n = Vec3::unit_vector(&n);
+        let sign = (1.0 as Float).copysign(n.z());
+        let a = -1.0 / (sign + n.z());
+        let b = n.x() * n.y() * a;
+        let t = Vec3::new(1.0 + sign * n.x() * n.x() * a, sign * b, -sign * n.x());
+        let bt = Vec3::new(b, sign + n.y() * n.y() * a, -n.y());
+        Mat3::from_cols(t, bt, n)
+    }
+
+    pub fn col(&self, i: usize) -> Vec3 {
+        Vec3::new(self.m[0][i], self.m[1][i], self.m[2][i])
+    }
+
+    pub fn transpose(&self) -> Mat3 {
+        Mat3::from_cols(
+            Vec3::new(self.m[0][0], self.m[0][1], self.m[0][2]),
+            Vec3::new(self.m[1][0], self.m[1][1], self.m[1][2]),
+            Vec3::new(self.m[2][0], self.m[2][1], self.m[2][2]),
+        )
+    }
+
+    pub fn determinant(&self) -> Float {
+        Vec3::dot(&self.col(0), &Vec3::cross(&self.col(1), &self.col(2)))
+    }
+
+    /// Inverse through the adjugate, or `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat3> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        // Rows of the inverse are the cross products of the columns
+        let r0 = Vec3::cross(&self.col(1), &self.col(2)) / det;
+        let r1 = Vec3::cross(&self.col(2), &self.col(0)) / det;
+        let r2 = Vec3::cross(&self.col(0), &self.col(1)) / det;
+        Some(Mat3::from_cols(r0, r1, r2).transpose())
+    }
+}
+
+impl ops::Mul for Mat3 {
+    type Output = Self;
+
+    fn mul(self, rhs: Mat3) -> Self::Output {
+        let mut m = [[0.0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat3::new(m)
+    }
+}
+
+impl ops::Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Self::Output {
+        self.col(0) * v.x() + self.col(1) * v.y() + self.col(2) * v.z()
+    }
+}
+
+impl From<Mat3> for Mat4 {
+    fn from(m: Mat3) -> Self {
+        let m = m.m;
+        Mat4::new([
+            [m[0][0], m[0][1], m[0][2], 0.0],
+            [m[1][0], m[1][1], m[1][2], 0.0],
+            [m[2][0], m[2][1], m[2][2], 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+/// Row-major 4x4 matrix for affine transforms of points, vectors and normals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub m: [[Float; 4]; 4],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::identity()
+    }
+}
+
+impl Mat4 {
+    pub fn new(m: [[Float; 4]; 4]) -> Mat4 {
+        Mat4 { m }
+    }
+
+    pub fn identity() -> Mat4 {
+        Mat4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        Mat4::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factor: Vec3) -> Mat4 {
+        Mat4::new([
+            [factor.x(), 0.0, 0.0, 0.0],
+            [0.0, factor.y(), 0.0, 0.0],
+            [0.0, 0.0, factor.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Rotation by `degrees` counter-clockwise around `axis`.
+    pub fn rotation(axis: Vec3, degrees: Float) -> Mat4 {
+        let a = Vec3::unit_vector(&axis);
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        Mat4::new([
+            [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y, 0.0],
+            [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x, 0.0],
+            [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut t = [[0.0; 4]; 4];
+        for (i, row) in t.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4::new(t)
+    }
+
+    /// Inverse by Gauss-Jordan elimination with partial pivoting, or `None`
+    /// if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let p = a[col][col];
+            for j in 0..4 {
+                a[col][j] /= p;
+                inv[col][j] /= p;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let f = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= f * a[col][j];
+                        inv[row][j] -= f * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Mat4::new(inv))
+    }
+
+    /// Transforms a point, including the translation.
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let (m, p) = (&self.m, p.0);
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        if w == 1.0 {
+            Point3::new(x, y, z)
+        } else {
+            Point3(Vec3::new(x, y, z) / w)
+        }
+    }
+
+    /// Transforms a point with absolute error bound `p_error`, returning the
+    /// new point and a bound that also covers the rounding of the transform.
+    /// Assumes an affine matrix.
+    pub fn transform_point_with_error(&self, p: Vec3, p_error: Vec3) -> (Vec3, Vec3) {
+        let m = &self.m;
+        let abs_row = |i: usize| Vec3::new(m[i][0].abs(), m[i][1].abs(), m[i][2].abs());
+        let (pa, ea) = (p.abs(), p_error);
+        let error = |i: usize| {
+            (gamma(3) + 1.0) * Vec3::dot(&abs_row(i), &ea)
+                + gamma(3) * (Vec3::dot(&abs_row(i), &pa) + m[i][3].abs())
+        };
+        (self.transform_point(Point3(p)).0, Vec3::new(error(0), error(1), error(2)))
+    }
+
+    /// Transforms a direction, ignoring the translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+
+    /// Transforms a normal. `self` must be the inverse of the matrix that
+    /// transforms points, since normals transform by the inverse transpose.
+    pub fn transform_normal(&self, n: Normal3) -> Normal3 {
+        let (m, n) = (&self.m, Vec3::from(n));
+        Normal3::new(Vec3::new(
+            m[0][0] * n.x() + m[1][0] * n.y() + m[2][0] * n.z(),
+            m[0][1] * n.x() + m[1][1] * n.y() + m[2][1] * n.z(),
+            m[0][2] * n.x() + m[1][2] * n.y() + m[2][2] * n.z(),
+        ))
+    }
+}
+
+impl ops::Mul<Point3> for Mat4 {
+    type Output = Point3;
+
+    fn mul(self, p: Point3) -> Self::Output {
+        self.transform_point(p)
+    }
+}
+
+impl ops::Mul<Vec3> for Mat4 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Self::Output {
+        self.transform_vector(v)
+    }
+}
+
+impl ops::Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Mat4) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4::new(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const CASES: usize = 1000;
+    const TOLERANCE: Float = 1e-4;
+
+    /// Random matrix with a diagonal large enough to dominate each row, so
+    /// it is invertible and not too badly conditioned.
+    fn random_mat3(rng: &mut StdRng) -> Mat3 {
+        let mut m = [[0.0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for value in row.iter_mut() {
+                *value = rng.gen_range(-1.0..1.0);
+            }
+            row[i] = (3.0 + rng.gen::<Float>()).copysign(row[i]);
+        }
+        Mat3::new(m)
+    }
+
+    fn random_mat4(rng: &mut StdRng) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for value in row.iter_mut() {
+                *value = rng.gen_range(-1.0..1.0);
+            }
+            row[i] = (4.0 + rng.gen::<Float>()).copysign(row[i]);
+        }
+        Mat4::new(m)
+    }
+
+    fn random_vec3(rng: &mut StdRng, range: Float) -> Vec3 {
+        Vec3::new(rng.gen_range(-range..range), rng.gen_range(-range..range), rng.gen_range(-range..range))
+    }
+
+    fn assert_mat3_close(a: Mat3, b: Mat3) {
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((a.m[i][j] - b.m[i][j]).abs() < TOLERANCE, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    fn assert_mat4_close(a: Mat4, b: Mat4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a.m[i][j] - b.m[i][j]).abs() < TOLERANCE, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn mat3_times_inverse_is_identity() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..CASES {
+            let m = random_mat3(&mut rng);
+            let inv = m.inverse().expect("diagonally dominant matrices are invertible");
+            assert_mat3_close(m * inv, Mat3::identity());
+            assert_mat3_close(inv * m, Mat3::identity());
+        }
+    }
+
+    #[test]
+    fn mat3_singular_has_no_inverse() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+        let m = Mat3::from_cols(a, a * 2.0, Vec3::new(0.0, 1.0, 0.0));
+        assert!(m.inverse().is_none());
+    }
+
+    #[test]
+    fn mat4_times_inverse_is_identity() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..CASES {
+            let m = random_mat4(&mut rng);
+            let inv = m.inverse().expect("diagonally dominant matrices are invertible");
+            assert_mat4_close(m * inv, Mat4::identity());
+            assert_mat4_close(inv * m, Mat4::identity());
+        }
+    }
+
+    #[test]
+    fn mat4_affine_inverse_undoes_transform() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..CASES {
+            let scale = Vec3::new(rng.gen_range(0.2..5.0), rng.gen_range(0.2..5.0), rng.gen_range(0.2..5.0));
+            let m = Mat4::translation(random_vec3(&mut rng, 10.0))
+                * Mat4::rotation(random_vec3(&mut rng, 1.0), rng.gen_range(-180.0..180.0))
+                * Mat4::scaling(scale);
+            let inv = m.inverse().expect("scales are nonzero");
+            assert_mat4_close(m * inv, Mat4::identity());
+
+            let p = random_vec3(&mut rng, 10.0);
+            let back = inv.transform_point(m * Point3(p));
+            assert!((back.0 - p).length() < TOLERANCE, "{:?} != {:?}", back, p);
+        }
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_to_transformed_surfaces() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..CASES {
+            let scale = Vec3::new(rng.gen_range(0.2..5.0), rng.gen_range(0.2..5.0), rng.gen_range(0.2..5.0));
+            let m = Mat4::rotation(random_vec3(&mut rng, 1.0), rng.gen_range(-180.0..180.0)) * Mat4::scaling(scale);
+            let inv = m.inverse().expect("scales are nonzero");
+            let tangent = random_vec3(&mut rng, 1.0);
+            let n = Normal3::new(Vec3::cross(&tangent, &random_vec3(&mut rng, 1.0)));
+            let transformed = Vec3::from(inv.transform_normal(n));
+            assert!((transformed.length() - 1.0).abs() < TOLERANCE);
+            assert!(Vec3::dot(&transformed, &m.transform_vector(tangent)).abs() < TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn mat4_singular_has_no_inverse() {
+        assert!(Mat4::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn onb_is_orthonormal_around_n() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..CASES {
+            let n = random_vec3(&mut rng, 1.0);
+            let basis = Mat3::onb(n);
+            assert_mat3_close(basis.transpose() * basis, Mat3::identity());
+            assert!((basis.col(2) - Vec3::unit_vector(&n)).length() < TOLERANCE);
+            assert!((basis.determinant() - 1.0).abs() < TOLERANCE);
+        }
+    }
+}
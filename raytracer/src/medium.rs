@@ -0,0 +1,330 @@
+use crate::float::Float;
+use crate::vec3::{Normal3, Vec3};
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::noise::fbm;
+use crate::sampler::{hash, to_unit_float};
+
+/// Random numbers for the walk along one ray, seeded from the ray itself so
+/// a pixel sample renders the same whichever thread runs it.
+struct RayRng {
+    seed: u64,
+    count: u64,
+}
+
+impl RayRng {
+    fn new(r: &Ray) -> RayRng {
+        let seed = r.origin().e.iter()
+            .chain(r.direction().e.iter())
+            .map(|c| c.to_ne_bytes().iter().fold(0, |bits, &byte| bits << 8 | byte as u64))
+            .fold(0, hash);
+        RayRng { seed, count: 0 }
+    }
+
+    /// Next value in `[0, 1)`.
+    fn next(&mut self) -> Float {
+        self.count += 1;
+        to_unit_float((hash(self.seed, self.count) >> 32) as u32)
+    }
+}
+
+/// Fog or smoke of constant density filling a closed boundary. A ray
+/// travelling through it scatters after an exponentially distributed
+/// distance, in a uniformly random direction. The boundary must implement
+/// `Hittable::hit_all`.
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable>,
+    neg_inv_density: Float,
+    phase: Material,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Box<dyn Hittable>, density: Float, albedo: Vec3) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase: Material::Isotropic { albedo },
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let crossings = self.boundary.hit_all(r);
+        let speed = r.direction().length();
+        let mut rng = RayRng::new(r);
+
+        // Crossings come in pairs of entering and leaving the volume
+        for span in crossings.chunks_exact(2) {
+            let t0 = span[0].t.max(t_min);
+            let t1 = span[1].t.min(t_max);
+            if t0 >= t1 {
+                continue;
+            }
+            let inside = (t1 - t0) * speed;
+            let hit_distance = self.neg_inv_density * (1.0 - rng.next()).ln();
+            if hit_distance < inside {
+                let t = t0 + hit_distance / speed;
+                // No surface here, so any normal will do
+                return Some(HitRecord {
+                    t,
+                    p: r.point_at_parameter(t),
+                    normal: Normal3::new(Vec3::new(1.0, 0.0, 0.0)),
+                    shading_normal: Normal3::new(Vec3::new(1.0, 0.0, 0.0)),
+                    front_face: true,
+                    material: self.phase.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+        None
+    }
+
+    /// Beer-Lambert attenuation over the length of the ray inside.
+    fn transmittance(&self, r: &Ray, t_min: Float, t_max: Float) -> Float {
+        let speed = r.direction().length();
+        let inside: Float = self.boundary.hit_all(r)
+            .chunks_exact(2)
+            .map(|span| (span[1].t.min(t_max) - span[0].t.max(t_min)).max(0.0) * speed)
+            .sum();
+        (inside / self.neg_inv_density).exp()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Density of a heterogeneous medium at a point of its box, given in
+/// coordinates that run from 0 to 1 across the box.
+pub enum Density {
+    /// Samples at the cell centres of a grid of `resolution` cells, stored
+    /// with x varying fastest and interpolated trilinearly.
+    Grid { resolution: [usize; 3], values: Vec<Float> },
+    /// Fractal noise clamped to `[0, 1]`, which leaves gaps between puffs.
+    Noise { frequency: Float, octaves: u32 },
+}
+
+impl Density {
+    pub fn at(&self, p: Vec3) -> Float {
+        match self {
+            Density::Grid { resolution, values } => {
+                let mut cell = [0; 3];
+                let mut frac = [0.0; 3];
+                for i in 0..3 {
+                    let x = (p.e[i] * resolution[i] as Float - 0.5).clamp(0.0, (resolution[i] - 1) as Float);
+                    cell[i] = (x as usize).min(resolution[i].saturating_sub(2));
+                    frac[i] = x - cell[i] as Float;
+                }
+                let value = |dx: usize, dy: usize, dz: usize| {
+                    let x = (cell[0] + dx).min(resolution[0] - 1);
+                    let y = (cell[1] + dy).min(resolution[1] - 1);
+                    let z = (cell[2] + dz).min(resolution[2] - 1);
+                    values[(z * resolution[1] + y) * resolution[0] + x]
+                };
+                let lerp = |t: Float, a: Float, b: Float| a + t * (b - a);
+                lerp(frac[2],
+                    lerp(frac[1], lerp(frac[0], value(0, 0, 0), value(1, 0, 0)), lerp(frac[0], value(0, 1, 0), value(1, 1, 0))),
+                    lerp(frac[1], lerp(frac[0], value(0, 0, 1), value(1, 0, 1)), lerp(frac[0], value(0, 1, 1), value(1, 1, 1))))
+            }
+            Density::Noise { frequency, octaves } => fbm(p * *frequency, *octaves).clamp(0.0, 1.0),
+        }
+    }
+
+    /// Upper bound of the density anywhere in the box.
+    pub fn max(&self) -> Float {
+        match self {
+            Density::Grid { values, .. } => values.iter().copied().fold(0.0, Float::max),
+            Density::Noise { .. } => 1.0,
+        }
+    }
+}
+
+/// Volume whose density varies over an axis aligned box, like a cloud. The
+/// extinction coefficient is `sigma_t` times the density. Free flights are
+/// sampled with delta tracking and transmittance estimated with ratio
+/// tracking, both against the maximum density as the majorant.
+pub struct HeterogeneousMedium {
+    bounds: Aabb,
+    density: Density,
+    sigma_t: Float,
+    phase: Material,
+}
+
+impl HeterogeneousMedium {
+    /// `g` is the Henyey-Greenstein asymmetry of the scattering.
+    pub fn new(bounds: Aabb, density: Density, sigma_t: Float, albedo: Vec3, g: Float) -> HeterogeneousMedium {
+        HeterogeneousMedium {
+            bounds,
+            density,
+            sigma_t,
+            phase: Material::HenyeyGreenstein { albedo, g },
+        }
+    }
+
+    fn sigma_t_at(&self, p: Vec3) -> Float {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let local = Vec3::new(
+            (p.x() - min.x()) / (max.x() - min.x()),
+            (p.y() - min.y()) / (max.y() - min.y()),
+            (p.z() - min.z()) / (max.z() - min.z()),
+        );
+        self.sigma_t * self.density.at(local)
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    /// Delta tracking: take exponential steps as if the whole box had the
+    /// majorant density, and accept each tentative collision with the ratio
+    /// of the real density to the majorant.
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t0, t1) = self.bounds.clip(r, t_min, t_max)?;
+        let majorant = self.sigma_t * self.density.max();
+        if majorant <= 0.0 {
+            return None;
+        }
+        let speed = r.direction().length();
+        let mut rng = RayRng::new(r);
+
+        let mut t = t0;
+        loop {
+            t -= (1.0 - rng.next()).ln() / (majorant * speed);
+            if t >= t1 {
+                return None;
+            }
+            let p = r.point_at_parameter(t);
+            if rng.next() * majorant < self.sigma_t_at(p) {
+                return Some(HitRecord {
+                    t,
+                    p,
+                    normal: Normal3::new(Vec3::new(1.0, 0.0, 0.0)),
+                    shading_normal: Normal3::new(Vec3::new(1.0, 0.0, 0.0)),
+                    front_face: true,
+                    material: self.phase.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    /// Ratio tracking: the same steps as delta tracking, but instead of
+    /// stopping at a collision the estimate is scaled by the chance of
+    /// passing it.
+    fn transmittance(&self, r: &Ray, t_min: Float, t_max: Float) -> Float {
+        let Some((t0, t1)) = self.bounds.clip(r, t_min, t_max) else {
+            return 1.0;
+        };
+        let majorant = self.sigma_t * self.density.max();
+        if majorant <= 0.0 {
+            return 1.0;
+        }
+        let speed = r.direction().length();
+        let mut rng = RayRng::new(r);
+
+        let mut transmittance = 1.0;
+        let mut t = t0;
+        loop {
+            t -= (1.0 - rng.next()).ln() / (majorant * speed);
+            if t >= t1 {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.sigma_t_at(r.point_at_parameter(t)) / majorant;
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::Sphere;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn fog() -> ConstantMedium {
+        let boundary = Sphere::new(Vec3::default(), 10.0, Material::default());
+        ConstantMedium::new(Box::new(boundary), 0.1, Vec3::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn constant_medium_repeats_for_the_same_ray() {
+        let fog = fog();
+        let r = Ray::new(Vec3::new(0.0, 0.0, -20.0), Vec3::new(0.01, 0.02, 1.0));
+        let first = fog.hit(&r, 0.0, Float::MAX).map(|rec| rec.t);
+        for _ in 0..10 {
+            assert_eq!(fog.hit(&r, 0.0, Float::MAX).map(|rec| rec.t), first);
+        }
+    }
+
+    #[test]
+    fn constant_medium_varies_between_rays() {
+        let fog = fog();
+        let hits: Vec<Option<Float>> = (0..20)
+            .map(|i| {
+                let r = Ray::new(Vec3::new(0.0, 0.0, -20.0), Vec3::new(0.001 * i as Float, 0.0, 1.0));
+                fog.hit(&r, 0.0, Float::MAX).map(|rec| rec.t)
+            })
+            .collect();
+        assert!(hits.iter().any(|t| *t != hits[0]));
+    }
+
+    fn cloud() -> HeterogeneousMedium {
+        let bounds = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+        let density = Density::Noise { frequency: 3.0, octaves: 4 };
+        HeterogeneousMedium::new(bounds, density, 2.0, Vec3::new(0.9, 0.9, 0.9), 0.3)
+    }
+
+    #[test]
+    fn heterogeneous_medium_repeats_for_the_same_ray() {
+        let cloud = cloud();
+        let r = Ray::new(Vec3::new(0.1, 0.2, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = cloud.hit(&r, 0.0, Float::MAX).map(|rec| rec.t);
+        let transmittance = cloud.transmittance(&r, 0.0, Float::MAX);
+        for _ in 0..10 {
+            assert_eq!(cloud.hit(&r, 0.0, Float::MAX).map(|rec| rec.t), hit);
+            assert_eq!(cloud.transmittance(&r, 0.0, Float::MAX), transmittance);
+        }
+    }
+
+    #[test]
+    fn tracking_through_a_constant_grid_matches_beer_lambert() {
+        // sigma_t of 1 over a box 2 deep along z
+        let bounds = Aabb::new(Vec3::default(), Vec3::new(1.0, 1.0, 2.0));
+        let density = Density::Grid { resolution: [2, 2, 2], values: vec![0.5; 8] };
+        let slab = HeterogeneousMedium::new(bounds, density, 2.0, Vec3::new(1.0, 1.0, 1.0), 0.0);
+
+        // Spread the rays over the face, as each ray seeds its own walk. The
+        // direction is not unit length, so t = 1 is 1 deep into the box.
+        const RAYS: usize = 20000;
+        let mut rng = StdRng::seed_from_u64(42);
+        let (mut passed, mut passed_half, mut transmittance, mut transmittance_half) = (0, 0, 0.0, 0.0);
+        for _ in 0..RAYS {
+            let origin = Vec3::new(rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0), -1.0);
+            let r = Ray::new(origin, Vec3::new(0.0, 0.0, 2.0));
+            match slab.hit(&r, 0.0, Float::MAX) {
+                None => passed += 1,
+                Some(rec) => {
+                    assert!((0.5..1.5).contains(&rec.t));
+                    passed_half += usize::from(rec.t >= 1.0);
+                }
+            }
+            transmittance += slab.transmittance(&r, 0.0, Float::MAX);
+            transmittance_half += slab.transmittance(&r, 0.0, 1.0);
+        }
+        passed_half += passed;
+
+        // Four standard deviations of the mean of RAYS coin flips at worst
+        let tolerance = 4.0 * (0.25 / RAYS as Float).sqrt();
+        let fraction = |n: usize| n as Float / RAYS as Float;
+        let (full, half) = ((-2.0 as Float).exp(), (-1.0 as Float).exp());
+        assert!((fraction(passed) - full).abs() < tolerance, "{} != {}", fraction(passed), full);
+        assert!((fraction(passed_half) - half).abs() < tolerance, "{} != {}", fraction(passed_half), half);
+        assert!((transmittance / RAYS as Float - full).abs() < tolerance);
+        assert!((transmittance_half / RAYS as Float - half).abs() < tolerance);
+    }
+}
@@ -0,0 +1,347 @@
+//! GGX (Trowbridge-Reitz) microfacet model and Fresnel terms. Directions
+//! are in a local shading frame with the normal along +z.
+
+use crate::float::Float;
+use crate::float::consts::PI;
+use crate::vec3::Vec3;
+
+/// Smallest roughness used, below that the lobe is numerically a mirror.
+const MIN_ALPHA: Float = 1e-4;
+
+/// Isotropic GGX distribution of microfacet normals.
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    alpha: Float,
+}
+
+impl Ggx {
+    /// Perceptual `roughness` in `[0, 1]`, squared into the GGX width.
+    pub fn from_roughness(roughness: Float) -> Ggx {
+        Ggx { alpha: (roughness * roughness).max(MIN_ALPHA) }
+    }
+
+    /// Density of microfacets with normal `m`.
+    pub fn d(&self, m: Vec3) -> Float {
+        if m.z() <= 0.0 {
+            return 0.0;
+        }
+        let cos2 = m.z() * m.z();
+        let tan2 = (1.0 - cos2) / cos2;
+        let a2 = self.alpha * self.alpha;
+        let e = 1.0 + tan2 / a2;
+        1.0 / (PI * a2 * cos2 * cos2 * e * e)
+    }
+
+    /// Smith's auxiliary function for the shadowing of direction `w`.
+    fn lambda(&self, w: Vec3) -> Float {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0.0 {
+            return Float::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Fraction of the microfacets facing `w` that are visible from it.
+    pub fn g1(&self, w: Vec3) -> Float {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Fraction visible from both `wo` and `wi`, height correlated.
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> Float {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal as seen from `wo`, which must be above
+    /// the surface (Heitz, "Sampling the GGX Distribution of Visible
+    /// Normals", 2018).
+    pub fn sample_visible_normal(&self, wo: Vec3, u: (Float, Float)) -> Vec3 {
+        // Stretch the view so the distribution becomes a hemisphere
+        let vh = Vec3::unit_vector(&Vec3::new(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()));
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / len2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(&vh, &t1);
+
+        // Uniform disk sample, squashed onto the part of the hemisphere seen from vh
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+        Vec3::unit_vector(&Vec3::new(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(1e-6)))
+    }
+
+    /// Density of `sample_visible_normal` returning `m`.
+    pub fn visible_normal_pdf(&self, wo: Vec3, m: Vec3) -> Float {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * Vec3::dot(&wo, &m).max(0.0) * self.d(m) / wo.z()
+    }
+
+    /// True when the distribution is so narrow that it is effectively a mirror.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha <= MIN_ALPHA
+    }
+
+    /// Cook-Torrance `D G / (4 cos_o cos_i)` for reflecting `wo` into `wi`
+    /// about `h`, without the Fresnel term.
+    pub fn reflection(&self, wo: Vec3, wi: Vec3, h: Vec3) -> Float {
+        self.d(h) * self.g(wo, wi) / (4.0 * wo.z() * wi.z())
+    }
+
+    /// Density of reflecting a visible normal sample about `h`, per unit
+    /// solid angle of the reflected direction.
+    pub fn reflection_pdf(&self, wo: Vec3, h: Vec3) -> Float {
+        self.visible_normal_pdf(wo, h) / (4.0 * Vec3::dot(&wo, &h))
+    }
+
+    /// Walter et al. microfacet transmission for refracting `wo` into `wi`
+    /// through `h`, without the Fresnel term. Includes the `1 / eta^2`
+    /// compression of radiance crossing into the other medium.
+    pub fn transmission(&self, wo: Vec3, wi: Vec3, h: Vec3, eta: Float) -> Float {
+        let (cos_o, cos_i) = (Vec3::dot(&wo, &h), Vec3::dot(&wi, &h));
+        let denom = cos_i + cos_o / eta;
+        self.d(h) * self.g(wo, wi) * (cos_i * cos_o).abs() / (denom * denom * (wi.z() * wo.z()).abs() * eta * eta)
+    }
+
+    /// Density of refracting a visible normal sample through `h`, per unit
+    /// solid angle of the refracted direction.
+    pub fn transmission_pdf(&self, wo: Vec3, wi: Vec3, h: Vec3, eta: Float) -> Float {
+        let cos_i = Vec3::dot(&wi, &h);
+        let denom = cos_i + Vec3::dot(&wo, &h) / eta;
+        self.visible_normal_pdf(wo, h) * cos_i.abs() / (denom * denom)
+    }
+}
+
+/// Microfacet normal that reflects `wo` into `wi`; both must be above the surface.
+pub fn reflection_half_vector(wo: Vec3, wi: Vec3) -> Option<Vec3> {
+    if wo.z() <= 0.0 || wi.z() <= 0.0 {
+        return None;
+    }
+    Some(Vec3::unit_vector(&(wo + wi)))
+}
+
+/// Microfacet normal that refracts `wo` into `wi`, on the side of `wo`.
+/// `None` when no microfacet can, e.g. both on the same side.
+pub fn refraction_half_vector(wo: Vec3, wi: Vec3, eta: Float) -> Option<Vec3> {
+    let h = wo + wi * eta;
+    if h.squared_length() == 0.0 {
+        return None;
+    }
+    let h = Vec3::unit_vector(&h);
+    let h = if h.z() < 0.0 { -h } else { h };
+    if Vec3::dot(&wo, &h) <= 0.0 || Vec3::dot(&wi, &h) >= 0.0 {
+        return None;
+    }
+    Some(h)
+}
+
+/// Unpolarized Fresnel reflectance of a conductor with complex index of
+/// refraction `eta + i k` per channel, from air at incidence cosine `cos_i`.
+pub fn fresnel_conductor(cos_i: Float, eta: Vec3, k: Vec3) -> Vec3 {
+    let cos2 = cos_i.clamp(0.0, 1.0) * cos_i.clamp(0.0, 1.0);
+    let sin2 = 1.0 - cos2;
+    let channel = |eta: Float, k: Float| {
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    };
+    Vec3::new(channel(eta.x(), k.x()), channel(eta.y(), k.y()), channel(eta.z(), k.z()))
+}
+
+/// Unpolarized Fresnel reflectance of a dielectric boundary, where `eta` is
+/// the index on the far side over the index on the incident side. Returns 1
+/// on total internal reflection.
+pub fn fresnel_dielectric(cos_i: Float, eta: Float) -> Float {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+/// Mirrors `wo` about the microfacet normal `m`; both point away from the surface.
+pub fn reflect(wo: Vec3, m: Vec3) -> Vec3 {
+    m * (2.0 * Vec3::dot(&wo, &m)) - wo
+}
+
+/// Bends `wo` through a boundary with normal `m` on its side, where `eta`
+/// is the relative index as in `fresnel_dielectric`. `None` on total
+/// internal reflection.
+pub fn refract(wo: Vec3, m: Vec3, eta: Float) -> Option<Vec3> {
+    let cos_i = Vec3::dot(&wo, &m);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + m * (cos_i / eta - cos_t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-6;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-4;
+
+    const CASES: usize = 1000;
+
+    /// Unit direction at polar angle `theta` from the normal in the xz plane.
+    fn at_angle(theta: Float) -> Vec3 {
+        Vec3::new(theta.sin(), 0.0, theta.cos())
+    }
+
+    fn random_direction(rng: &mut StdRng) -> Vec3 {
+        let z: Float = rng.gen_range(0.01..1.0);
+        let phi = rng.gen_range(0.0..2.0 * PI);
+        let r = (1.0 - z * z).sqrt();
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    #[test]
+    fn ggx_projected_area_is_one() {
+        // The distribution only depends on theta, and cos(theta) sin(theta)
+        // d(theta) = d(cos^2) / 2 integrates evenly in the squared cosine
+        const STEPS: usize = 100000;
+        for roughness in [0.2, 0.5, 0.8, 1.0] {
+            let ggx = Ggx::from_roughness(roughness);
+            // Summed in rows so single precision does not drift
+            let area: Float = (0..STEPS / 1000)
+                .map(|row| {
+                    (row * 1000..(row + 1) * 1000)
+                        .map(|i| {
+                            let cos2 = (i as Float + 0.5) / STEPS as Float;
+                            ggx.d(Vec3::new((1.0 - cos2).sqrt(), 0.0, cos2.sqrt())) * PI / STEPS as Float
+                        })
+                        .sum::<Float>()
+                })
+                .sum();
+            assert!((area - 1.0).abs() < 1e-3, "roughness {}: {}", roughness, area);
+        }
+    }
+
+    #[test]
+    fn visible_normal_samples_follow_their_pdf() {
+        // Histogram over bins of cos(theta) and phi, against the pdf
+        // integrated over each bin with dw = d(cos theta) d(phi)
+        const SAMPLES: usize = 200000;
+        const BINS: usize = 8;
+        const SUBSTEPS: usize = 16;
+        let mut rng = StdRng::seed_from_u64(43);
+        for (roughness, theta_o) in [(0.7, 1.0), (0.5, 1.4), (1.0, 0.2)] {
+            let (ggx, wo) = (Ggx::from_roughness(roughness), at_angle(theta_o));
+            let bin = |m: Vec3| {
+                let phi = m.y().atan2(m.x()) + PI;
+                let i = ((m.z() * BINS as Float) as usize).min(BINS - 1);
+                let j = ((phi / (2.0 * PI) * BINS as Float) as usize).min(BINS - 1);
+                i * BINS + j
+            };
+
+            let mut counts = [0usize; BINS * BINS];
+            for _ in 0..SAMPLES {
+                let m = ggx.sample_visible_normal(wo, (rng.gen(), rng.gen()));
+                assert!((m.length() - 1.0).abs() < TOLERANCE && m.z() > 0.0);
+                assert!(Vec3::dot(&wo, &m) >= -TOLERANCE);
+                counts[bin(m)] += 1;
+            }
+
+            let mut expected = [0.0; BINS * BINS];
+            let (dz, dphi) = (1.0 / (BINS * SUBSTEPS) as Float, 2.0 * PI / (BINS * SUBSTEPS) as Float);
+            for a in 0..BINS * SUBSTEPS {
+                for b in 0..BINS * SUBSTEPS {
+                    let z = (a as Float + 0.5) * dz;
+                    let phi = (b as Float + 0.5) * dphi - PI;
+                    let r = (1.0 - z * z).sqrt();
+                    let m = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+                    expected[bin(m)] += ggx.visible_normal_pdf(wo, m) * dz * dphi;
+                }
+            }
+
+            for (count, p) in counts.iter().zip(expected) {
+                let fraction = *count as Float / SAMPLES as Float;
+                let tolerance = 4.0 * (p * (1.0 - p) / SAMPLES as Float).sqrt() + 2e-3;
+                assert!((fraction - p).abs() < tolerance, "roughness {}, theta {}: {} != {}", roughness, theta_o, fraction, p);
+            }
+        }
+    }
+
+    #[test]
+    fn fresnel_at_normal_incidence() {
+        for eta in [1.33, 1.5, 2.4, 1.0 / 1.5] {
+            let r0 = ((eta - 1.0) / (eta + 1.0)) * ((eta - 1.0) / (eta + 1.0));
+            assert!((fresnel_dielectric(1.0, eta) - r0).abs() < TOLERANCE, "eta {}", eta);
+        }
+        assert!(fresnel_dielectric(1.0, 1.0).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn fresnel_past_the_critical_angle() {
+        // Leaving glass for air, the critical angle has sin = 1 / 1.5
+        let eta: Float = 1.0 / 1.5;
+        let critical_cos = (1.0 - eta * eta).sqrt();
+        for cos_i in [0.0, 0.1, 0.5, critical_cos - 1e-3] {
+            assert_eq!(fresnel_dielectric(cos_i, eta), 1.0);
+        }
+        assert!(fresnel_dielectric(critical_cos + 1e-2, eta) < 1.0);
+        // Going into the denser medium there is no critical angle
+        assert!(fresnel_dielectric(0.01, 1.5) < 1.0);
+    }
+
+    #[test]
+    fn reflect_and_refract_obey_snell() {
+        let mut rng = StdRng::seed_from_u64(43);
+        for _ in 0..CASES {
+            let (wo, m) = (random_direction(&mut rng), random_direction(&mut rng));
+            if Vec3::dot(&wo, &m) <= 0.01 {
+                continue;
+            }
+            let eta = rng.gen_range(0.5..2.5);
+            let cos_o = Vec3::dot(&wo, &m);
+            let plane = Vec3::cross(&wo, &m);
+
+            // Mirror image: same angle to m, in the plane of wo and m
+            let wi = reflect(wo, m);
+            assert!((wi.length() - 1.0).abs() < TOLERANCE);
+            assert!((Vec3::dot(&wi, &m) - cos_o).abs() < TOLERANCE);
+            assert!(Vec3::dot(&plane, &wi).abs() < TOLERANCE);
+            assert!(Vec3::cross(&(wo + wi), &m).length() < TOLERANCE);
+
+            let sin_o = (1.0 - cos_o * cos_o).sqrt();
+            match refract(wo, m, eta) {
+                None => assert!(sin_o > eta - TOLERANCE),
+                Some(wt) => {
+                    assert!((wt.length() - 1.0).abs() < TOLERANCE);
+                    let cos_t = Vec3::dot(&wt, &m);
+                    assert!(cos_t < 0.0);
+                    assert!((sin_o - eta * (1.0 - cos_t * cos_t).sqrt()).abs() < 1e1 * TOLERANCE);
+                    assert!(Vec3::dot(&plane, &wt).abs() < TOLERANCE);
+                    // The tangential parts point opposite ways
+                    assert!(Vec3::dot(&(wo - m * cos_o), &(wt - m * cos_t)) <= TOLERANCE);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Procedural noise for textures and volume densities.
+
+use std::sync::OnceLock;
+
+use rand::SeedableRng;
+use rand::seq::SliceRandom;
+
+use crate::float::Float;
+use crate::vec3::Vec3;
+
+/// Ken Perlin's permutation of 0..256, repeated so lookups never wrap. Seeded
+/// so the noise is the same every run.
+fn permutation() -> &'static [u8; 512] {
+    static TABLE: OnceLock<[u8; 512]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut p: Vec<u8> = (0..=255).collect();
+        p.shuffle(&mut rand::rngs::StdRng::seed_from_u64(0x5eed));
+        let mut table = [0; 512];
+        for (i, value) in table.iter_mut().enumerate() {
+            *value = p[i % 256];
+        }
+        table
+    })
+}
+
+fn fade(t: Float) -> Float {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: Float, a: Float, b: Float) -> Float {
+    a + t * (b - a)
+}
+
+/// Dot product of the offset with one of twelve edge directions of a cube.
+fn grad(hash: u8, x: Float, y: Float, z: Float) -> Float {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Improved Perlin noise, roughly in `[-1, 1]` and zero at integer points.
+pub fn perlin(p: Vec3) -> Float {
+    let perm = permutation();
+    let cell = [p.x().floor(), p.y().floor(), p.z().floor()];
+    let [x, y, z] = [p.x() - cell[0], p.y() - cell[1], p.z() - cell[2]];
+    let [xi, yi, zi] = cell.map(|c| (c as i64 & 255) as usize);
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+
+    let a = perm[xi] as usize + yi;
+    let (aa, ab) = (perm[a] as usize + zi, perm[a + 1] as usize + zi);
+    let b = perm[xi + 1] as usize + yi;
+    let (ba, bb) = (perm[b] as usize + zi, perm[b + 1] as usize + zi);
+
+    lerp(w,
+        lerp(v,
+            lerp(u, grad(perm[aa], x, y, z), grad(perm[ba], x - 1.0, y, z)),
+            lerp(u, grad(perm[ab], x, y - 1.0, z), grad(perm[bb], x - 1.0, y - 1.0, z))),
+        lerp(v,
+            lerp(u, grad(perm[aa + 1], x, y, z - 1.0), grad(perm[ba + 1], x - 1.0, y, z - 1.0)),
+            lerp(u, grad(perm[ab + 1], x, y - 1.0, z - 1.0), grad(perm[bb + 1], x - 1.0, y - 1.0, z - 1.0))))
+}
+
+/// Fractal sum of `octaves` layers of noise, each at twice the frequency and
+/// half the amplitude of the last. Stays roughly within `[-2, 2]`.
+pub fn fbm(p: Vec3, octaves: u32) -> Float {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut p = p;
+    for _ in 0..octaves {
+        sum += amplitude * perlin(p);
+        amplitude *= 0.5;
+        p = p * 2.0;
+    }
+    sum
+}
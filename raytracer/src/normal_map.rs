@@ -0,0 +1,169 @@
+use crate::float::Float;
+use crate::vec3::{Normal3, Vec3};
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::matrix::Mat3;
+use crate::aabb::Aabb;
+use crate::texture::Texture;
+
+/// Step in UV space for the finite differences of a bump map.
+const BUMP_DELTA: Float = 1e-3;
+
+/// How the shading normal is tilted away from the geometric one.
+#[derive(Debug, Clone)]
+pub enum NormalMap {
+    /// Tangent space normal map, usually from an image: red along `dpdu`,
+    /// green along the bitangent and blue along the normal, each mapped from
+    /// `[0, 1]` to `[-1, 1]`.
+    Tangent(Texture),
+    /// Height field over the surface. The grey value times `scale` is how far
+    /// the surface would be pushed out along its normal.
+    Bump { height: Texture, scale: Float },
+}
+
+impl NormalMap {
+    /// Perturbed normal on the outward side of the surface.
+    fn shading_normal(&self, rec: &HitRecord, outward: Vec3) -> Vec3 {
+        // Shapes without UV derivatives still get a consistent frame
+        let (dpdu, dpdv) = if rec.dpdu.squared_length() > 0.0 && rec.dpdv.squared_length() > 0.0 {
+            (rec.dpdu, rec.dpdv)
+        } else {
+            let axes = Mat3::onb(outward);
+            (axes.col(0), axes.col(1))
+        };
+
+        let normal = match self {
+            NormalMap::Tangent(texture) => {
+                let tangent = Vec3::unit_vector(&(dpdu - outward * Vec3::dot(&outward, &dpdu)));
+                let bitangent = Vec3::cross(&outward, &tangent);
+                let c = texture.value(rec.u, rec.v, rec.p) * 2.0 - Vec3::new(1.0, 1.0, 1.0);
+                tangent * c.x() + bitangent * c.y() + outward * c.z()
+            }
+            NormalMap::Bump { height, scale } => {
+                // Displaced surface p + h n, differentiated along u and v
+                let h = |u: Float, v: Float, p: Vec3| scale * height.scalar(u, v, p);
+                let base = h(rec.u, rec.v, rec.p);
+                let dh_du = (h(rec.u + BUMP_DELTA, rec.v, rec.p + dpdu * BUMP_DELTA) - base) / BUMP_DELTA;
+                let dh_dv = (h(rec.u, rec.v + BUMP_DELTA, rec.p + dpdv * BUMP_DELTA) - base) / BUMP_DELTA;
+                let normal = Vec3::cross(&(dpdu + outward * dh_du), &(dpdv + outward * dh_dv));
+                // The UVs may be left handed with respect to the normal
+                if Vec3::dot(&normal, &outward) < 0.0 { -normal } else { normal }
+            }
+        };
+
+        if normal.squared_length() > 0.0 && Vec3::dot(&normal, &outward) > 0.0 {
+            Vec3::unit_vector(&normal)
+        } else {
+            outward
+        }
+    }
+
+    /// Tilts the shading normal of `rec`, keeping it on the side of `normal`.
+    pub fn apply(&self, rec: &mut HitRecord) {
+        let outward = Vec3::from(if rec.front_face { rec.normal } else { -rec.normal });
+        let shading = Normal3::new(self.shading_normal(rec, outward));
+        rec.shading_normal = if rec.front_face { shading } else { -shading };
+    }
+}
+
+/// Adds surface detail to `object` by tilting its shading normals with a
+/// `NormalMap`. The geometry, and with it shadows and silhouettes, stays the
+/// same.
+pub struct NormalMapped {
+    object: Box<dyn Hittable>,
+    map: NormalMap,
+}
+
+impl NormalMapped {
+    pub fn new(object: Box<dyn Hittable>, map: NormalMap) -> NormalMapped {
+        NormalMapped { object, map }
+    }
+}
+
+impl Hittable for NormalMapped {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let mut rec = self.object.hit(r, t_min, t_max)?;
+        self.map.apply(&mut rec);
+        Some(rec)
+    }
+
+    fn hit_all(&self, r: &Ray) -> Vec<HitRecord> {
+        let mut hits = self.object.hit_all(r);
+        for rec in &mut hits {
+            self.map.apply(rec);
+        }
+        hits
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: Float, t_max: Float) -> Float {
+        self.object.transmittance(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::material::Material;
+    use crate::sphere::Sphere;
+    use crate::texture::Image;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::sync::Arc;
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-9;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-5;
+
+    const CASES: usize = 1000;
+
+    /// A sphere, which has UV derivatives, and a cube, which has none.
+    fn shapes() -> [Box<dyn Hittable>; 2] {
+        [
+            Box::new(Sphere::new(Vec3::default(), 1.0, Material::default())),
+            Box::new(Cube::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0), Material::default())),
+        ]
+    }
+
+    /// Largest change of the shading normal by `map` over random hits from
+    /// outside and inside each shape.
+    fn largest_tilt(map: NormalMap) -> Float {
+        let mut rng = StdRng::seed_from_u64(47);
+        let mut tilt: Float = 0.0;
+        for shape in shapes() {
+            let mapped = NormalMapped::new(shape, map.clone());
+            for _ in 0..CASES {
+                let origin = if rng.gen_bool(0.5) { Vec3::new(0.0, 0.0, -5.0) } else { Vec3::default() };
+                let direction = Vec3::new(rng.gen_range(-0.1..0.1), rng.gen_range(-0.1..0.1), 1.0);
+                let r = Ray::new(origin, direction);
+                let rec = mapped.hit(&r, 0.0, Float::MAX).unwrap();
+                let plain = mapped.object.hit(&r, 0.0, Float::MAX).unwrap();
+                tilt = tilt.max((Vec3::from(rec.shading_normal) - Vec3::from(plain.shading_normal)).length());
+            }
+        }
+        tilt
+    }
+
+    #[test]
+    fn flat_normal_map_keeps_the_normal() {
+        let flat = Vec3::new(0.5, 0.5, 1.0);
+        assert!(largest_tilt(NormalMap::Tangent(Texture::Constant(flat))) < TOLERANCE);
+        let image = Image::new(2, 2, vec![flat; 4]);
+        assert!(largest_tilt(NormalMap::Tangent(Texture::Image(Arc::new(image)))) < TOLERANCE);
+        // While a map leaning along the tangent does tilt it
+        assert!(largest_tilt(NormalMap::Tangent(Texture::Constant(Vec3::new(1.0, 0.5, 1.0)))) > 0.1);
+    }
+
+    #[test]
+    fn constant_bump_map_keeps_the_normal() {
+        for scale in [0.1, 1.0, 10.0] {
+            let bump = NormalMap::Bump { height: Texture::constant(0.7), scale };
+            assert!(largest_tilt(bump) < TOLERANCE, "scale {}", scale);
+        }
+    }
+}
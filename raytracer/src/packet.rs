@@ -0,0 +1,48 @@
+use crate::float::Float;
+use crate::float::FloatX4;
+
+use crate::ray::Ray;
+
+/// Four rays stored component-wise, one ray per SIMD lane, so a single
+/// intersection test can run on all of them at once. Works best for
+/// coherent rays such as neighbouring camera rays. The renderer still
+/// traces single rays, so for now only `benches/simd.rs` uses packets.
+#[derive(Debug, Clone, Copy)]
+pub struct RayPacket4 {
+    pub origin: [FloatX4; 3],
+    pub direction: [FloatX4; 3],
+    /// Reciprocal of the direction, for slab tests against boxes.
+    pub inv_direction: [FloatX4; 3],
+}
+
+impl RayPacket4 {
+    pub fn new(rays: &[Ray; 4]) -> RayPacket4 {
+        let lane = |f: &dyn Fn(&Ray) -> Float| FloatX4::new([f(&rays[0]), f(&rays[1]), f(&rays[2]), f(&rays[3])]);
+        let origin = [
+            lane(&|r| r.origin().x()),
+            lane(&|r| r.origin().y()),
+            lane(&|r| r.origin().z()),
+        ];
+        let direction = [
+            lane(&|r| r.direction().x()),
+            lane(&|r| r.direction().y()),
+            lane(&|r| r.direction().z()),
+        ];
+        let one = FloatX4::splat(1.0);
+        RayPacket4 {
+            origin,
+            direction,
+            inv_direction: [one / direction[0], one / direction[1], one / direction[2]],
+        }
+    }
+
+    pub fn ray(&self, lane: usize) -> Ray {
+        let o = self.origin.map(|c| c.to_array()[lane]);
+        let d = self.direction.map(|c| c.to_array()[lane]);
+        Ray::new(crate::vec3::Vec3::new(o[0], o[1], o[2]), crate::vec3::Vec3::new(d[0], d[1], d[2]))
+    }
+}
+
+pub(crate) fn dot(a: &[FloatX4; 3], b: &[FloatX4; 3]) -> FloatX4 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
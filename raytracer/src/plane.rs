@@ -1,66 +1,154 @@
+use crate::float::{Float, gamma};
+use crate::float::consts::PI;
 use crate::vec3::Vec3;
 use crate::ray::Ray;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::matrix::Mat3;
 
+/// Thickness given to the bounding boxes of flat shapes.
+pub(crate) const FLAT_BOX_DELTA: Float = 1e-4;
+
+/// Infinite plane of points `x` with `dot(x, normal) = distance`.
 pub struct Plane {
     pub normal: Vec3,
-    pub distance: f64,
+    pub distance: Float,
     pub material: Material,
 }
 
 impl Plane {
-    pub fn plane(normal: Vec3, distance: f64, material: Material) -> Plane {
+    pub fn new(normal: Vec3, distance: Float, material: Material) -> Plane {
         Plane {
             normal,
             distance,
             material,
         }
-    } 
+    }
 }
 
 impl Hittable for Plane {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let t = (Vec3::dot(&-ray.origin(), &self.normal) + self.distance) / Vec3::dot(&ray.direction(), &self.normal);
-        if t < t_min || t > t_max {
-            return None;
-        }
-        let p = ray.point_at_parameter(t);
-        Some(HitRecord {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let t = hit_plane(ray, self.normal, self.distance, t_min, t_max)?;
+        let (p, p_error) = project_onto_plane(ray.point_at_parameter(t), self.normal, self.distance);
+        // Unbounded coordinates along two axes in the plane, so textures tile
+        let axes = Mat3::onb(self.normal);
+        let mut rec = HitRecord {
             t,
             p,
-            normal: self.normal,
-            material: self.material,
-        })
+            p_error,
+            u: Vec3::dot(&p, &axes.col(0)),
+            v: Vec3::dot(&p, &axes.col(1)),
+            dpdu: axes.col(0),
+            dpdv: axes.col(1),
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(ray, Vec3::unit_vector(&self.normal));
+        Some(rec)
+    }
+}
+
+/// Parameter where `ray` crosses the plane `dot(x, normal) = distance`, or
+/// `None` if it lies outside `(t_min, t_max)` or the ray runs parallel to
+/// the plane.
+fn hit_plane(ray: &Ray, normal: Vec3, distance: Float, t_min: Float, t_max: Float) -> Option<Float> {
+    let denom = Vec3::dot(&ray.direction(), &normal);
+    if denom.abs() <= Float::EPSILON * ray.direction().length() * normal.length() {
+        return None;
+    }
+    let t = (distance - Vec3::dot(&ray.origin(), &normal)) / denom;
+    if t <= t_min || t >= t_max {
+        return None;
     }
+    Some(t)
+}
+
+/// Snaps `p` onto the plane `dot(x, normal) = distance` and bounds the error
+/// of the result. The error in `t` along a grazing ray can be huge, but after
+/// projection only the rounding of the projection itself is left.
+fn project_onto_plane(p: Vec3, normal: Vec3, distance: Float) -> (Vec3, Vec3) {
+    let p = p - normal * ((Vec3::dot(&p, &normal) - distance) / normal.squared_length());
+    let p_error = (p.abs() + Vec3::new(1.0, 1.0, 1.0) * distance.abs()) * gamma(5);
+    (p, p_error)
 }
 
-pub struct Aabb {
-    min: Vec3,
-    max: Vec3,
+/// Parallelogram spanned by the edges `u` and `v` from the corner `q`. The
+/// outward normal is `u x v`, and the UVs run from 0 to 1 along each edge.
+pub struct Quad {
+    q: Vec3,
+    u: Vec3,
+    v: Vec3,
+    /// `n / |n|^2` for the unnormalized normal `n`, turns a point in the
+    /// plane into its coordinates along `u` and `v`.
+    w: Vec3,
+    normal: Vec3,
+    distance: Float,
+    material: Material,
 }
 
-impl Aabb {  
-    pub fn new(min: Vec3, max: Vec3 ) -> Aabb {
-        Aabb {
-            min,
-            max,
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: Material) -> Quad {
+        let n = Vec3::cross(&u, &v);
+        let normal = Vec3::unit_vector(&n);
+        Quad {
+            q,
+            u,
+            v,
+            w: n / n.squared_length(),
+            normal,
+            distance: Vec3::dot(&normal, &q),
+            material,
         }
     }
-    fn hit(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
-        for a in 0..3 {
-            let t0 = min_max::min((self.min.e[a] - ray.origin().e[a]) / ray.direction().e[a], (self.max.e[a] - ray.origin().e[a]) / ray.direction().e[a]);
-            let t1 = min_max::max((self.min.e[a] - ray.origin().e[a]) / ray.direction().e[a], (self.max.e[a] - ray.origin().e[a]) / ray.direction().e[a]);
-            t_min = min_max::max(t0, t_min);
-            t_max = min_max::min(t1, t_max);
-            if t_max <= t_min {
-                return false;
-            }
+
+    /// Rectangle of `width` by `height` centred on `center`, facing `normal`.
+    pub fn rectangle(center: Vec3, normal: Vec3, width: Float, height: Float, material: Material) -> Quad {
+        let axes = Mat3::onb(normal);
+        let u = axes.col(0) * width;
+        let v = axes.col(1) * height;
+        Quad::new(center - u / 2.0 - v / 2.0, u, v, material)
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let t = hit_plane(ray, self.normal, self.distance, t_min, t_max)?;
+        let planar = ray.point_at_parameter(t) - self.q;
+        let alpha = Vec3::dot(&self.w, &Vec3::cross(&planar, &self.v));
+        let beta = Vec3::dot(&self.w, &Vec3::cross(&self.u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
         }
-        true
+
+        let (p, p_error) = project_onto_plane(ray.point_at_parameter(t), self.normal, self.distance);
+        let mut rec = HitRecord {
+            t,
+            p,
+            p_error,
+            u: alpha,
+            v: beta,
+            dpdu: self.u,
+            dpdv: self.v,
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(ray, self.normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let corners = [self.q, self.q + self.u, self.q + self.v, self.q + self.u + self.v];
+        Some(Aabb::from_points(&corners, FLAT_BOX_DELTA))
     }
 }
 
+/// Parallelogram with the corner `p0` and edges running to `p1` and `p2`,
+/// inside a bounding box given by the caller. The normal is
+/// `(p1 - p0) x (p2 - p0)`. `Quad` works out its own box.
+///
+/// A flat box is padded to `FLAT_BOX_DELTA` thick, as `Aabb::clip` misses
+/// every ray against a box with no volume.
 pub struct Rectangle {
     pub p0: Vec3,
     pub p1: Vec3,
@@ -70,61 +158,265 @@ pub struct Rectangle {
 }
 
 impl Rectangle {
-    pub fn rectangle(
-        p0: Vec3,
-        p1: Vec3,
-        p2: Vec3,
-        material: Material,
-        aabb: Aabb,
-        ) -> Rectangle {
+    pub fn new(p0: Vec3, p1: Vec3, p2: Vec3, material: Material, aabb: Aabb) -> Rectangle {
         Rectangle {
             p0,
             p1,
             p2,
             material,
-            aabb,
+            aabb: Aabb::from_points(&[aabb.min(), aabb.max()], FLAT_BOX_DELTA),
         }
-    }  
+    }
 }
 
 impl Hittable for Rectangle {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
         if !self.aabb.hit(ray, t_min, t_max) {
             return None;
         }
 
-        // Calculate the normal vector of the rectangle
-        let normal = Vec3::unit_vector(&Vec3::cross(&(self.p1 - self.p0), &(self.p2 - self.p0)));
+        let (u, v) = (self.p1 - self.p0, self.p2 - self.p0);
+        let n = Vec3::cross(&u, &v);
+        let normal = Vec3::unit_vector(&n);
+        let distance = Vec3::dot(&normal, &self.p0);
+        let t = hit_plane(ray, normal, distance, t_min, t_max)?;
 
-        // Check if the ray is parallel to the rectangle
-        let t = Vec3::dot(&(self.p0 - ray.origin()), &normal) / Vec3::dot(&ray.direction(), &normal);
-        if t < t_min || t > t_max {
+        // Coordinates of the hit along each edge, in [0, 1] inside
+        let planar = ray.point_at_parameter(t) - self.p0;
+        let w = n / n.squared_length();
+        let alpha = Vec3::dot(&w, &Vec3::cross(&planar, &v));
+        let beta = Vec3::dot(&w, &Vec3::cross(&u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
             return None;
         }
 
-        // Calculate the intersection point
-        let p = ray.point_at_parameter(t);
+        let (p, p_error) = project_onto_plane(ray.point_at_parameter(t), normal, distance);
+        let mut rec = HitRecord {
+            t,
+            p,
+            p_error,
+            u: alpha,
+            v: beta,
+            dpdu: u,
+            dpdv: v,
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(ray, normal);
+        Some(rec)
+    }
 
-        // Check if the intersection point is within the bounds of the rectangle
-        Vec3::dot(&(self.p1 - self.p0), &(self.p2 - self.p0));
-        let d1 = Vec3::dot(&(self.p1 - self.p0), &(p - self.p0));
-        let d2 = Vec3::dot(&(self.p1 - self.p0), &(self.p2 - self.p0));
-        if d1 < 0.0 || d2 < d1 {
-            return None;
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.aabb)
+    }
+}
+
+/// Flat disk facing `normal`, optionally with a hole in the middle. The u
+/// coordinate runs once around the centre and v from the rim inwards.
+pub struct Disk {
+    center: Vec3,
+    axes: Mat3,
+    radius: Float,
+    inner_radius: Float,
+    material: Material,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: Float, material: Material) -> Disk {
+        Disk::annulus(center, normal, 0.0, radius, material)
+    }
+
+    /// Ring between `inner_radius` and `radius`.
+    pub fn annulus(center: Vec3, normal: Vec3, inner_radius: Float, radius: Float, material: Material) -> Disk {
+        Disk {
+            center,
+            axes: Mat3::onb(normal),
+            radius,
+            inner_radius,
+            material,
         }
+    }
+
+    fn normal(&self) -> Vec3 {
+        self.axes.col(2)
+    }
+}
 
-        let d3 = Vec3::dot(&(self.p2 - self.p0), &(p - self.p0));
-        let d4 = Vec3::dot(&(self.p2 - self.p0), &(self.p1 - self.p0));
-        if d3 < 0.0 || d4 < d3 {
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let distance = Vec3::dot(&self.normal(), &self.center);
+        let t = hit_plane(ray, self.normal(), distance, t_min, t_max)?;
+        let (p, p_error) = project_onto_plane(ray.point_at_parameter(t), self.normal(), distance);
+        let local = p - self.center;
+        let r2 = local.squared_length();
+        if r2 > self.radius * self.radius || r2 < self.inner_radius * self.inner_radius {
             return None;
         }
 
-        // Return a hit record
-        Some(HitRecord {
+        let (x, y) = (Vec3::dot(&local, &self.axes.col(0)), Vec3::dot(&local, &self.axes.col(1)));
+        let phi = y.atan2(x);
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+        let r = r2.sqrt();
+        let mut rec = HitRecord {
             t,
             p,
-            normal,
-            material: self.material,
-        })
+            p_error,
+            u: phi / (2.0 * PI),
+            v: (self.radius - r) / (self.radius - self.inner_radius),
+            dpdu: (self.axes.col(1) * x - self.axes.col(0) * y) * (2.0 * PI),
+            dpdv: if r > 0.0 { local * (-(self.radius - self.inner_radius) / r) } else { Vec3::default() },
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(ray, self.normal());
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // A circle of radius r around n reaches r * sqrt(1 - n_i^2) along axis i
+        let n = self.normal();
+        let extent = Vec3::new(
+            self.radius * (1.0 - n.x() * n.x()).max(0.0).sqrt(),
+            self.radius * (1.0 - n.y() * n.y()).max(0.0).sqrt(),
+            self.radius * (1.0 - n.z() * n.z()).max(0.0).sqrt(),
+        );
+        Some(Aabb::from_points(&[self.center - extent, self.center + extent], FLAT_BOX_DELTA))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: Float = 1e-6;
+
+    fn down(x: Float, z: Float) -> Ray {
+        Ray::new(Vec3::new(x, 5.0, z), Vec3::new(0.0, -1.0, 0.0))
+    }
+
+    /// Unit square in the y = 0 plane from the origin, facing up.
+    fn unit_quad() -> Quad {
+        Quad::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0), Material::default())
     }
-}
\ No newline at end of file
+
+    fn contains(aabb: &Aabb, p: Vec3) -> bool {
+        (0..3).all(|i| aabb.min().e[i] <= p.e[i] + TOLERANCE && p.e[i] <= aabb.max().e[i] + TOLERANCE)
+    }
+
+    #[test]
+    fn parallel_rays_miss_the_plane() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let along = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(hit_plane(&along, normal, 0.0, 0.0, Float::MAX).is_none());
+        let inside = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 1.0));
+        assert!(hit_plane(&inside, normal, 0.0, 0.0, Float::MAX).is_none());
+
+        let plane = Plane::new(normal, 0.0, Material::default());
+        assert!(plane.hit(&along, 0.0, Float::MAX).is_none());
+        let rec = plane.hit(&down(3.0, -2.0), 0.0, Float::MAX).unwrap();
+        assert!((rec.t - 5.0).abs() < TOLERANCE);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn plane_respects_t_range() {
+        let plane = Plane::new(Vec3::new(0.0, 1.0, 0.0), 0.0, Material::default());
+        assert!(plane.hit(&down(0.0, 0.0), 0.0, 4.0).is_none());
+        assert!(plane.hit(&down(0.0, 0.0), 6.0, Float::MAX).is_none());
+        assert!(plane.bounding_box().is_none());
+    }
+
+    #[test]
+    fn quad_accepts_edges_and_rejects_outside() {
+        let quad = unit_quad();
+        for (x, z) in [(0.5, 0.5), (0.0, 0.5), (1.0, 0.5), (0.5, 0.0), (0.5, 1.0), (0.0, 0.0), (1.0, 1.0)] {
+            assert!(quad.hit(&down(x, z), 0.0, Float::MAX).is_some(), "missed ({}, {})", x, z);
+        }
+        for (x, z) in [(-0.01, 0.5), (1.01, 0.5), (0.5, -0.01), (0.5, 1.01), (2.0, 2.0)] {
+            assert!(quad.hit(&down(x, z), 0.0, Float::MAX).is_none(), "hit ({}, {})", x, z);
+        }
+    }
+
+    #[test]
+    fn quad_uvs_run_along_the_edges() {
+        let quad = unit_quad();
+        // u along the first edge (z), v along the second (x)
+        let rec = quad.hit(&down(0.25, 0.75), 0.0, Float::MAX).unwrap();
+        assert!((rec.u - 0.75).abs() < TOLERANCE && (rec.v - 0.25).abs() < TOLERANCE);
+        for i in 0..=10 {
+            for j in 0..=10 {
+                let rec = quad.hit(&down(i as Float / 10.0, j as Float / 10.0), 0.0, Float::MAX).unwrap();
+                assert!((0.0..=1.0).contains(&rec.u) && (0.0..=1.0).contains(&rec.v));
+            }
+        }
+        let rec = quad.hit(&down(1.0, 1.0), 0.0, Float::MAX).unwrap();
+        assert!((rec.u - 1.0).abs() < TOLERANCE && (rec.v - 1.0).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn quad_bounding_box_holds_corners_and_has_thickness() {
+        let quad = Quad::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Material::default());
+        let aabb = quad.bounding_box().unwrap();
+        for corner in [Vec3::new(1.0, 2.0, 3.0), Vec3::new(3.0, 2.0, 3.0), Vec3::new(1.0, 2.0, 2.0), Vec3::new(3.0, 2.0, 2.0)] {
+            assert!(contains(&aabb, corner));
+        }
+        assert!(aabb.max().y() - aabb.min().y() >= FLAT_BOX_DELTA * 0.99);
+        // A ray straight down onto the flat box still finds it
+        assert!(aabb.hit(&Ray::new(Vec3::new(2.0, 5.0, 2.5), Vec3::new(0.0, -1.0, 0.0)), 0.0, Float::MAX));
+    }
+
+    #[test]
+    fn rectangle_accepts_long_thin_shapes() {
+        // The old inside test compared against the dot product of the two
+        // edges and rejected most of any rectangle that was not square
+        let rectangle = Rectangle::new(
+            Vec3::default(),
+            Vec3::new(4.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Material::default(),
+            Aabb::new(Vec3::default(), Vec3::new(4.0, 0.0, 1.0)),
+        );
+        assert!(rectangle.hit(&down(3.5, 0.5), 0.0, Float::MAX).is_some());
+        assert!(rectangle.hit(&down(4.5, 0.5), 0.0, Float::MAX).is_none());
+        assert!(rectangle.hit(&down(2.0, 1.5), 0.0, Float::MAX).is_none());
+        let aabb = rectangle.bounding_box().unwrap();
+        assert_eq!(aabb.max().x(), 4.0);
+        assert!(aabb.max().y() - aabb.min().y() >= FLAT_BOX_DELTA * 0.99);
+    }
+
+    #[test]
+    fn annulus_rejects_the_hole() {
+        let ring = Disk::annulus(Vec3::default(), Vec3::new(0.0, 1.0, 0.0), 1.0, 2.0, Material::default());
+        assert!(ring.hit(&down(0.0, 0.0), 0.0, Float::MAX).is_none());
+        assert!(ring.hit(&down(0.9, 0.0), 0.0, Float::MAX).is_none());
+        assert!(ring.hit(&down(1.5, 0.0), 0.0, Float::MAX).is_some());
+        assert!(ring.hit(&down(0.0, -1.9), 0.0, Float::MAX).is_some());
+        assert!(ring.hit(&down(2.1, 0.0), 0.0, Float::MAX).is_none());
+
+        // v runs from 0 at the rim to 1 at the hole
+        let rim = ring.hit(&down(2.0 - 1e-9, 0.0), 0.0, Float::MAX).unwrap();
+        let hole = ring.hit(&down(1.0 + 1e-9, 0.0), 0.0, Float::MAX).unwrap();
+        assert!(rim.v.abs() < 1e-3 && (hole.v - 1.0).abs() < 1e-3);
+
+        let disk = Disk::new(Vec3::default(), Vec3::new(0.0, 1.0, 0.0), 2.0, Material::default());
+        assert!(disk.hit(&down(0.0, 0.0), 0.0, Float::MAX).is_some());
+    }
+
+    #[test]
+    fn disk_bounding_box_holds_the_rim() {
+        let center = Vec3::new(1.0, -2.0, 0.5);
+        let normal = Vec3::unit_vector(&Vec3::new(1.0, 2.0, -0.5));
+        let disk = Disk::new(center, normal, 3.0, Material::default());
+        let aabb = disk.bounding_box().unwrap();
+        let axes = Mat3::onb(normal);
+        for i in 0..64 {
+            let phi = 2.0 * PI * i as Float / 64.0;
+            let rim = center + (axes.col(0) * phi.cos() + axes.col(1) * phi.sin()) * 3.0;
+            assert!(contains(&aabb, rim), "{:?} outside {:?}", rim, aabb);
+        }
+
+        // Facing up it is exactly as wide as the disk
+        let flat = Disk::new(Vec3::default(), Vec3::new(0.0, 1.0, 0.0), 3.0, Material::default()).bounding_box().unwrap();
+        assert!((flat.max().x() - 3.0).abs() < TOLERANCE && (flat.min().z() + 3.0).abs() < TOLERANCE);
+    }
+}
@@ -1,14 +1,12 @@
 extern crate image;
 
 use crate::vec3::Vec3;
-#[cfg(test)]
-use crate::ray::Ray;
 
 use std::path::Path;
 
 use indicatif::{ProgressBar, ProgressStyle};
 
-pub fn gen_ppm(img: Vec<Vec<Vec3>>, filename: String) -> () {
+pub fn gen_ppm(img: Vec<Vec<Vec3>>, filename: String) {
 
     // Time to write to image file!
     let path = Path::new(&filename);
@@ -29,7 +27,7 @@ pub fn gen_ppm(img: Vec<Vec<Vec3>>, filename: String) -> () {
         bar.inc(1);
     }
 
-    let _ = image::DynamicImage::ImageRgb8(imgbuf).save(&path);
+    let _ = image::DynamicImage::ImageRgb8(imgbuf).save(path);
     
     bar.finish();
     println!("successfully wrote to {}", display);
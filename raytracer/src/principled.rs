@@ -0,0 +1,270 @@
+//! Principled material after the glTF 2.0 metallic-roughness model and its
+//! transmission, clearcoat and sheen extensions. All directions are in the
+//! local shading frame with the normal along +z, both pointing away from the
+//! surface, and `wo` is always above it.
+
+use crate::float::Float;
+use crate::float::consts::PI;
+use crate::microfacet::{self, Ggx};
+use crate::sampler::sample_cosine_hemisphere;
+use crate::vec3::Vec3;
+
+/// Fresnel reflectance of the clearcoat layer at normal incidence.
+const CLEARCOAT_F0: Float = 0.04;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Principled {
+    pub base_color: Vec3,
+    /// 0 for a dielectric, 1 for a metal tinted by `base_color`.
+    pub metallic: Float,
+    pub roughness: Float,
+    /// Strength of the dielectric reflection. 0.5 is 4% at normal incidence,
+    /// an index of refraction of 1.5, and 1 doubles that.
+    pub specular: Float,
+    /// Fraction of the dielectric base that refracts instead of diffusing.
+    pub transmission: Float,
+    /// Strength of a smooth, colourless varnish on top of everything else.
+    pub clearcoat: Float,
+    pub clearcoat_roughness: Float,
+    /// Soft white rim reflection at grazing angles, as on cloth.
+    pub sheen: Float,
+    pub emission: Vec3,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Principled {
+            base_color: Vec3::new(0.8, 0.8, 0.8),
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            transmission: 0.0,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            sheen: 0.0,
+            emission: Vec3::default(),
+        }
+    }
+}
+
+/// Probabilities of sampling each lobe, in the order diffuse, specular
+/// reflection, transmission and clearcoat.
+struct LobeWeights([Float; 4]);
+
+impl Principled {
+    /// Index of refraction of the inside over the outside implied by `specular`.
+    fn ior(&self) -> Float {
+        let sqrt_f0 = (0.08 * self.specular).clamp(0.0, 0.99).sqrt();
+        (1.0 + sqrt_f0) / (1.0 - sqrt_f0)
+    }
+
+    /// Relative index across the boundary for light arriving from `wo`'s side.
+    fn eta(&self, front_face: bool) -> Float {
+        if front_face { self.ior() } else { 1.0 / self.ior() }
+    }
+
+    /// Splits the dielectric part by its Fresnel reflectance seen from `wo`,
+    /// so glass mostly refracts and plastic mostly diffuses.
+    fn lobe_weights(&self, wo: Vec3, eta: Float) -> LobeWeights {
+        let dielectric = 1.0 - self.metallic;
+        let fresnel = microfacet::fresnel_dielectric(wo.z(), eta);
+        let weights = [
+            dielectric * (1.0 - self.transmission) * (1.0 - fresnel),
+            self.metallic + dielectric * fresnel,
+            dielectric * self.transmission * (1.0 - fresnel),
+            0.25 * self.clearcoat,
+        ];
+        let total: Float = weights.iter().sum();
+        LobeWeights(weights.map(|w| w / total))
+    }
+
+    /// Reflectance of the base specular lobe for a microfacet at cosine `cos`
+    /// to `wo`: dielectric Fresnel blended into tinted Schlick for metals.
+    fn specular_fresnel(&self, cos: Float, eta: Float) -> Vec3 {
+        let dielectric = microfacet::fresnel_dielectric(cos, eta);
+        let metal = schlick(self.base_color, cos);
+        Vec3::new(dielectric, dielectric, dielectric) * (1.0 - self.metallic) + metal * self.metallic
+    }
+
+    /// BSDF value for light arriving along `wi` and leaving along `wo`,
+    /// without the cosine factor.
+    pub fn eval(&self, wo: Vec3, wi: Vec3, front_face: bool) -> Vec3 {
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return Vec3::default();
+        }
+        let eta = self.eta(front_face);
+        let coat = self.clearcoat * schlick_scalar(CLEARCOAT_F0, wo.z());
+        let base_scale = 1.0 - coat;
+        let ggx = Ggx::from_roughness(self.roughness);
+
+        if wi.z() < 0.0 {
+            let Some(h) = microfacet::refraction_half_vector(wo, wi, eta) else {
+                return Vec3::default();
+            };
+            let fresnel = microfacet::fresnel_dielectric(Vec3::dot(&wo, &h), eta);
+            let weight = (1.0 - self.metallic) * self.transmission * (1.0 - fresnel);
+            return self.base_color * (weight * ggx.transmission(wo, wi, h, eta) * base_scale);
+        }
+
+        let Some(h) = microfacet::reflection_half_vector(wo, wi) else {
+            return Vec3::default();
+        };
+        // Past the critical angle of `h` the Fresnel term is 1, so this also
+        // carries the light the transmission lobe reflects there
+        let cos_h = Vec3::dot(&wo, &h);
+        let specular = self.specular_fresnel(cos_h, eta) * ggx.reflection(wo, wi, h);
+
+        // What the dielectric reflection lets through reaches the diffuse base
+        let fresnel = microfacet::fresnel_dielectric(cos_h, eta);
+        let diffuse_weight = (1.0 - self.metallic) * (1.0 - self.transmission) * (1.0 - fresnel);
+        let sheen = self.sheen * (1.0 - self.metallic) * (1.0 - cos_h).powi(5);
+        let diffuse = self.base_color * (diffuse_weight / PI) + Vec3::new(sheen, sheen, sheen);
+
+        let coat_ggx = Ggx::from_roughness(self.clearcoat_roughness);
+        let clearcoat = self.clearcoat * schlick_scalar(CLEARCOAT_F0, cos_h) * coat_ggx.reflection(wo, wi, h);
+
+        (diffuse + specular) * base_scale + Vec3::new(clearcoat, clearcoat, clearcoat)
+    }
+
+    /// Density of `sample` returning `wi`, per unit solid angle.
+    pub fn pdf(&self, wo: Vec3, wi: Vec3, front_face: bool) -> Float {
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return 0.0;
+        }
+        let eta = self.eta(front_face);
+        let LobeWeights([diffuse, specular, transmission, clearcoat]) = self.lobe_weights(wo, eta);
+        let ggx = Ggx::from_roughness(self.roughness);
+        if wi.z() < 0.0 {
+            return match microfacet::refraction_half_vector(wo, wi, eta) {
+                Some(h) => transmission * ggx.transmission_pdf(wo, wi, h, eta),
+                None => 0.0,
+            };
+        }
+        let Some(h) = microfacet::reflection_half_vector(wo, wi) else {
+            return 0.0;
+        };
+        // Transmission samples past the critical angle of their microfacet
+        // reflect instead, so they land here too
+        let total_internal = if microfacet::refract(wo, h, eta).is_none() { transmission } else { 0.0 };
+        diffuse * wi.z() / PI
+            + (specular + total_internal) * ggx.reflection_pdf(wo, h)
+            + clearcoat * Ggx::from_roughness(self.clearcoat_roughness).reflection_pdf(wo, h)
+    }
+
+    /// Picks a lobe with `u_lobe` and samples a direction from it with `u`.
+    /// Returns the direction, the BSDF value and the density of the whole
+    /// mixture, so the estimate is `f |cos| / pdf`.
+    pub fn sample(&self, wo: Vec3, front_face: bool, u_lobe: Float, u: (Float, Float)) -> Option<(Vec3, Vec3, Float)> {
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let eta = self.eta(front_face);
+        let LobeWeights([diffuse, specular, transmission, _]) = self.lobe_weights(wo, eta);
+        let (wi, reflected) = if u_lobe < diffuse {
+            (sample_cosine_hemisphere(u), true)
+        } else if u_lobe < diffuse + specular {
+            let m = Ggx::from_roughness(self.roughness).sample_visible_normal(wo, u);
+            (microfacet::reflect(wo, m), true)
+        } else if u_lobe < diffuse + specular + transmission {
+            let m = Ggx::from_roughness(self.roughness).sample_visible_normal(wo, u);
+            // Past the critical angle of this microfacet all of it reflects
+            match microfacet::refract(wo, m, eta) {
+                Some(wi) => (wi, false),
+                None => (microfacet::reflect(wo, m), true),
+            }
+        } else {
+            let m = Ggx::from_roughness(self.clearcoat_roughness).sample_visible_normal(wo, u);
+            (microfacet::reflect(wo, m), true)
+        };
+        // A steep microfacet can send the sample to the other side of the
+        // surface, where `pdf` would score it as a different path
+        if reflected != (wi.z() > 0.0) {
+            return None;
+        }
+
+        let pdf = self.pdf(wo, wi, front_face);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some((wi, self.eval(wo, wi, front_face), pdf))
+    }
+}
+
+fn schlick(f0: Vec3, cos: Float) -> Vec3 {
+    let w = (1.0 - cos.clamp(0.0, 1.0)).powi(5);
+    f0 * (1.0 - w) + Vec3::new(w, w, w)
+}
+
+fn schlick_scalar(f0: Float, cos: Float) -> Float {
+    f0 + (1.0 - f0) * (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-2;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 2e-2;
+
+    /// Direction at polar angle `theta` from the normal in the xz plane.
+    fn at_angle(theta: Float) -> Vec3 {
+        Vec3::new(theta.sin(), 0.0, theta.cos())
+    }
+
+    /// Integral of `f` over the upper and lower hemispheres with the
+    /// midpoint rule in cos(theta) and phi.
+    fn integrate(f: impl Fn(Vec3) -> Float) -> (Float, Float) {
+        const STEPS: usize = 400;
+        let (dz, dphi) = (2.0 / STEPS as Float, 2.0 * PI / STEPS as Float);
+        let mut sums = (0.0, 0.0);
+        for a in 0..STEPS {
+            let z = -1.0 + (a as Float + 0.5) * dz;
+            let r = (1.0 - z * z).sqrt();
+            let row: Float = (0..STEPS)
+                .map(|b| {
+                    let phi = (b as Float + 0.5) * dphi;
+                    f(Vec3::new(r * phi.cos(), r * phi.sin(), z)) * dz * dphi
+                })
+                .sum();
+            if z > 0.0 { sums.0 += row } else { sums.1 += row }
+        }
+        sums
+    }
+
+    #[test]
+    fn sampled_directions_follow_the_pdf() {
+        // Inside rough glass close to the critical angle many microfacets
+        // reflect everything, and from outside at a grazing angle
+        let glass = Principled { base_color: Vec3::new(1.0, 1.0, 1.0), roughness: 0.8, transmission: 1.0, ..Default::default() };
+        const SAMPLES: usize = 100000;
+        let mut rng = StdRng::seed_from_u64(44);
+        for (theta, front_face) in [(0.5, false), (0.7, false), (1.4, true)] {
+            let wo = at_angle(theta);
+
+            // Where the samples land, and the estimate of the albedo
+            let (mut up, mut down, mut albedo) = (0, 0, 0.0);
+            for _ in 0..SAMPLES {
+                let u = (rng.gen::<Float>(), rng.gen::<Float>());
+                let Some((wi, f, pdf)) = glass.sample(wo, front_face, rng.gen(), u) else {
+                    continue;
+                };
+                assert!((pdf - glass.pdf(wo, wi, front_face)).abs() <= 1e-6 * pdf);
+                if wi.z() > 0.0 { up += 1 } else { down += 1 }
+                albedo += f.x() * wi.z().abs() / pdf;
+            }
+            let fraction = |n: usize| n as Float / SAMPLES as Float;
+
+            let (pdf_up, pdf_down) = integrate(|wi| glass.pdf(wo, wi, front_face));
+            assert!((fraction(up) - pdf_up).abs() < TOLERANCE, "theta {}: {} != {}", theta, fraction(up), pdf_up);
+            assert!((fraction(down) - pdf_down).abs() < TOLERANCE, "theta {}: {} != {}", theta, fraction(down), pdf_down);
+
+            let (f_up, f_down) = integrate(|wi| glass.eval(wo, wi, front_face).x() * wi.z().abs());
+            let albedo = albedo / SAMPLES as Float;
+            assert!((albedo - (f_up + f_down)).abs() < 2.0 * TOLERANCE, "theta {}: {} != {}", theta, albedo, f_up + f_down);
+        }
+    }
+}
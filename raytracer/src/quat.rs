@@ -0,0 +1,206 @@
+use std::ops;
+
+use crate::float::Float;
+use crate::vec3::Vec3;
+use crate::matrix::{Mat3, Mat4};
+
+/// Unit quaternion `w + xi + yj + zk` representing a rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub w: Float,
+    pub v: Vec3,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::identity()
+    }
+}
+
+impl Quat {
+    pub fn new(w: Float, v: Vec3) -> Quat {
+        Quat { w, v }
+    }
+
+    pub fn identity() -> Quat {
+        Quat::new(1.0, Vec3::default())
+    }
+
+    /// Rotation by `degrees` counter-clockwise around `axis`.
+    pub fn from_axis_angle(axis: Vec3, degrees: Float) -> Quat {
+        let (sin, cos) = (degrees.to_radians() / 2.0).sin_cos();
+        Quat::new(cos, Vec3::unit_vector(&axis) * sin)
+    }
+
+    pub fn dot(a: &Quat, b: &Quat) -> Float {
+        a.w * b.w + Vec3::dot(&a.v, &b.v)
+    }
+
+    pub fn length(self) -> Float {
+        Quat::dot(&self, &self).sqrt()
+    }
+
+    pub fn normalize(self) -> Quat {
+        let l = self.length();
+        Quat::new(self.w / l, self.v / l)
+    }
+
+    /// Inverse rotation of a unit quaternion.
+    pub fn conjugate(self) -> Quat {
+        Quat::new(self.w, -self.v)
+    }
+
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        // v' = v + 2w(q x v) + 2q x (q x v)
+        let t = 2.0 * Vec3::cross(&self.v, &v);
+        v + self.w * t + Vec3::cross(&self.v, &t)
+    }
+
+    /// Spherical linear interpolation along the shorter arc between `a` and `b`.
+    pub fn slerp(a: Quat, b: Quat, t: Float) -> Quat {
+        let mut cos_theta = Quat::dot(&a, &b);
+        let b = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Quat::new(-b.w, -b.v)
+        } else {
+            b
+        };
+
+        // Nearly parallel quaternions fall back to normalized lerp
+        if cos_theta > 0.9995 {
+            return Quat::new(a.w + t * (b.w - a.w), a.v + t * (b.v - a.v)).normalize();
+        }
+
+        let theta = cos_theta.min(1.0).acos();
+        let sin_theta = theta.sin();
+        let wa = ((1.0 - t) * theta).sin() / sin_theta;
+        let wb = (t * theta).sin() / sin_theta;
+        Quat::new(wa * a.w + wb * b.w, wa * a.v + wb * b.v)
+    }
+
+    pub fn to_mat3(self) -> Mat3 {
+        Mat3::from_cols(
+            self.rotate(Vec3::new(1.0, 0.0, 0.0)),
+            self.rotate(Vec3::new(0.0, 1.0, 0.0)),
+            self.rotate(Vec3::new(0.0, 0.0, 1.0)),
+        )
+    }
+
+    pub fn to_mat4(self) -> Mat4 {
+        Mat4::from(self.to_mat3())
+    }
+}
+
+/// Hamilton product: `a * b` rotates by `b` first, then by `a`.
+impl ops::Mul for Quat {
+    type Output = Self;
+
+    fn mul(self, rhs: Quat) -> Self::Output {
+        Quat::new(
+            self.w * rhs.w - Vec3::dot(&self.v, &rhs.v),
+            self.w * rhs.v + rhs.w * self.v + Vec3::cross(&self.v, &rhs.v),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const CASES: usize = 1000;
+    const TOLERANCE: Float = 1e-4;
+
+    fn random_vec3(rng: &mut StdRng) -> Vec3 {
+        Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0))
+    }
+
+    fn random_quat(rng: &mut StdRng) -> Quat {
+        Quat::from_axis_angle(random_vec3(rng), rng.gen_range(-360.0..360.0))
+    }
+
+    /// Whether `a` and `b` are the same rotation; `q` and `-q` both are.
+    fn same_rotation(a: Quat, b: Quat) -> bool {
+        (Quat::dot(&a, &b).abs() - 1.0).abs() < TOLERANCE
+    }
+
+    fn assert_mat3_close(a: Mat3, b: Mat3) {
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((a.m[i][j] - b.m[i][j]).abs() < TOLERANCE, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn slerp_hits_endpoints() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..CASES {
+            let (a, b) = (random_quat(&mut rng), random_quat(&mut rng));
+            assert!(same_rotation(Quat::slerp(a, b, 0.0), a));
+            assert!(same_rotation(Quat::slerp(a, b, 1.0), b));
+        }
+    }
+
+    #[test]
+    fn slerp_stays_unit_length() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..CASES {
+            let (a, b) = (random_quat(&mut rng), random_quat(&mut rng));
+            let t = rng.gen::<Float>();
+            assert!((Quat::slerp(a, b, t).length() - 1.0).abs() < TOLERANCE);
+        }
+        // The nearly parallel fallback too
+        let a = Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 10.0);
+        let b = Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 10.5);
+        assert!((Quat::slerp(a, b, 0.3).length() - 1.0).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn slerp_turns_at_constant_speed() {
+        let a = Quat::identity();
+        let b = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), 120.0);
+        let expected = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), 30.0);
+        assert!(same_rotation(Quat::slerp(a, b, 0.25), expected));
+    }
+
+    #[test]
+    fn matrix_rotates_like_quaternion() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..CASES {
+            let q = random_quat(&mut rng);
+            let v = random_vec3(&mut rng);
+            assert!((q.to_mat3() * v - q.rotate(v)).length() < TOLERANCE);
+            assert!((q.to_mat4().transform_vector(v) - q.rotate(v)).length() < TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn matrix_matches_axis_angle_rotation() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..CASES {
+            let axis = random_vec3(&mut rng);
+            let degrees = rng.gen_range(-360.0..360.0);
+            let q = Quat::from_axis_angle(axis, degrees).to_mat4();
+            let m = Mat4::rotation(axis, degrees);
+            for i in 0..4 {
+                for j in 0..4 {
+                    assert!((q.m[i][j] - m.m[i][j]).abs() < TOLERANCE, "{:?} != {:?}", q, m);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn matrix_round_trips_through_products_and_inverses() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..CASES {
+            let (a, b) = (random_quat(&mut rng), random_quat(&mut rng));
+            assert_mat3_close((a * b).to_mat3(), a.to_mat3() * b.to_mat3());
+            assert_mat3_close(a.conjugate().to_mat3(), a.to_mat3().inverse().unwrap());
+            assert_mat3_close(a.to_mat3() * a.to_mat3().transpose(), Mat3::identity());
+            assert!((a.to_mat3().determinant() - 1.0).abs() < TOLERANCE);
+        }
+    }
+}
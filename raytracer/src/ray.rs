@@ -1,25 +1,111 @@
+use crate::float::Float;
 use crate::vec3::Vec3;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Ray {
-    A: Vec3,
-    B: Vec3,
+    origin: Vec3,
+    direction: Vec3,
 }
 
 impl Ray {
-    pub fn ray(a: Vec3, b: Vec3) -> Ray {
-        Ray { A: a, B: b }
+    pub fn new(origin: Vec3, direction: Vec3) -> Ray {
+        Ray { origin, direction }
     }
 
     pub fn origin(self) -> Vec3 {
-        self.A
+        self.origin
     }
 
     pub fn direction(self) -> Vec3 {
-        self.B
+        self.direction
     }
 
-    pub fn point_at_parameter(self, t: f64) -> Vec3 {
-        self.A +  self.B * t
+    pub fn point_at_parameter(self, t: Float) -> Vec3 {
+        self.origin + self.direction * t
     }
-}
\ No newline at end of file
+}
+
+/// Moves a hit point `p` with absolute error bound `p_error` off the surface
+/// with geometric normal `n`, far enough along the side `w` points to that a
+/// ray leaving from it can never hit the same surface again. The offset
+/// scales with the error, so it works for tiny objects and huge planes alike
+/// (PBRT, "Robust Spawned Ray Origins").
+pub fn offset_ray_origin(p: Vec3, p_error: Vec3, n: Vec3, w: Vec3) -> Vec3 {
+    let d = Vec3::dot(&n.abs(), &p_error);
+    let mut offset = n * d;
+    if Vec3::dot(&w, &n) < 0.0 {
+        offset = -offset;
+    }
+    let mut po = p + offset;
+    // Round away from p so the offset is not lost to rounding
+    for i in 0..3 {
+        if offset.e[i] > 0.0 {
+            po.e[i] = po.e[i].next_up();
+        } else if offset.e[i] < 0.0 {
+            po.e[i] = po.e[i].next_down();
+        }
+    }
+    po
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::material::Material;
+    use crate::plane::Plane;
+    use crate::sampler::sample_unit_sphere;
+
+    const CASES: usize = 10_000;
+    /// The fixed `t_min` rays used to be traced with.
+    #[cfg(not(feature = "f32"))]
+    const OLD_T_MIN: Float = 1e-3;
+    #[cfg(feature = "f32")]
+    const OLD_T_MIN: Float = 1e-2;
+
+    /// A tilted plane so far from the origin that its hit points carry
+    /// rounding errors much larger than any sensible fixed epsilon.
+    fn far_plane() -> Plane {
+        #[cfg(not(feature = "f32"))]
+        let distance = 1e15;
+        #[cfg(feature = "f32")]
+        let distance = 1e6;
+        Plane::new(Vec3::unit_vector(&Vec3::new(0.3, 1.0, 0.2)), distance, Material::default())
+    }
+
+    /// Hits on the far plane from rays leaving near the origin, each with a
+    /// random direction to continue in from the hit.
+    fn hits(plane: &Plane, seed: u64) -> Vec<(HitRecord, Vec3)> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut hits = Vec::new();
+        while hits.len() < CASES {
+            let origin = sample_unit_sphere(rng.gen());
+            let direction = sample_unit_sphere(rng.gen());
+            if let Some(rec) = plane.hit(&Ray::new(origin, direction), 0.0, Float::MAX) {
+                hits.push((rec, sample_unit_sphere(rng.gen())));
+            }
+        }
+        hits
+    }
+
+    #[test]
+    fn spawned_rays_never_rehit_a_huge_plane() {
+        let plane = far_plane();
+        for (rec, wi) in hits(&plane, 1) {
+            let spawned = rec.spawn_ray(wi);
+            assert!(plane.hit(&spawned, 0.0, Float::MAX).is_none(), "acne at {:?} towards {:?}", rec.p, wi);
+        }
+    }
+
+    #[test]
+    fn fixed_epsilon_rehits_a_huge_plane() {
+        let plane = far_plane();
+        let acne = hits(&plane, 1)
+            .into_iter()
+            .filter(|(rec, wi)| plane.hit(&Ray::new(rec.p, *wi), OLD_T_MIN, Float::MAX).is_some())
+            .count();
+        assert!(acne > 0, "the fixed epsilon was expected to fail on a plane this far away");
+    }
+}
@@ -0,0 +1,394 @@
+use std::sync::Mutex;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::iter::{ParallelBridge, ParallelIterator};
+
+use crate::float::Float;
+use crate::camera::Camera;
+use crate::filter::Filter;
+use crate::framebuffer::Framebuffer;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::emitted;
+use crate::ray::Ray;
+use crate::sampler::{Sampler, SamplerKind, make_sampler};
+use crate::scene::Scene;
+use crate::vec3::Vec3;
+
+/// Order in which tiles are handed out to the worker threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileOrder {
+    /// Row by row from the top left corner.
+    Scanline,
+    /// Outwards from the centre of the image, so the interesting part shows up first.
+    Spiral,
+    /// Along a Hilbert curve, which keeps consecutive tiles close together.
+    Hilbert,
+}
+
+impl TileOrder {
+    pub fn from_name(name: &str) -> Option<TileOrder> {
+        match name {
+            "scanline" => Some(TileOrder::Scanline),
+            "spiral" => Some(TileOrder::Spiral),
+            "hilbert" => Some(TileOrder::Hilbert),
+            _ => None,
+        }
+    }
+}
+
+/// Pixel rectangle `[x0, x1) x [y0, y1)` of the full frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropWindow {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl CropWindow {
+    pub fn new(x0: u32, y0: u32, x1: u32, y1: u32) -> CropWindow {
+        CropWindow { x0, y0, x1, y1 }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    pub samples: u32,
+    pub sampler: SamplerKind,
+    pub filter: Filter,
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    /// Only pixels inside the window are rendered, the rest stay black.
+    pub crop: Option<CropWindow>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            width: 400,
+            height: 300,
+            samples: 100,
+            sampler: SamplerKind::Sobol,
+            filter: Filter::default(),
+            tile_size: 32,
+            tile_order: TileOrder::Spiral,
+            crop: None,
+        }
+    }
+}
+
+/// Renders the scene tile by tile. Tiles are pulled in `tile_order` by
+/// rayon's worker threads as they become idle, and merged into the
+/// framebuffer as soon as each one finishes.
+pub fn render(scene: &Scene, cam: &Camera, settings: &RenderSettings) -> Framebuffer {
+    let crop = settings.crop.unwrap_or(CropWindow::new(0, 0, settings.width, settings.height));
+    let crop = CropWindow::new(
+        crop.x0.min(settings.width),
+        crop.y0.min(settings.height),
+        crop.x1.min(settings.width),
+        crop.y1.min(settings.height),
+    );
+    let tiles = tiles(crop, settings.tile_size.max(1), settings.tile_order);
+
+    let bar = ProgressBar::new(tiles.len() as u64);
+    bar.set_style(ProgressStyle::default_bar().template("[{elapsed} elapsed] {wide_bar:.cyan/white} {percent}% [{eta} remaining] [rendering]").ok().unwrap());
+
+    let framebuffer = Mutex::new(Framebuffer::new(settings.width, settings.height));
+    let base_sampler = make_sampler(settings.sampler, settings.samples);
+
+    tiles.into_iter().par_bridge().for_each(|bounds| {
+        let mut tile = framebuffer.lock().unwrap().tile(bounds.x0, bounds.y0, bounds.x1, bounds.y1, settings.filter);
+        let mut sampler = base_sampler.clone_box();
+        for y in bounds.y0..bounds.y1 {
+            for x in bounds.x0..bounds.x1 {
+                sampler.start_pixel(x, y);
+                for s in 0..sampler.samples_per_pixel() {
+                    sampler.start_sample(s);
+                    let (dx, dy) = sampler.get_2d();
+                    let film_x = x as Float + dx;
+                    let film_y = y as Float + dy;
+                    let u: Float = film_x / settings.width as Float;
+                    let v: Float = 1.0 - film_y / settings.height as Float;
+                    let r: Ray = cam.get_ray(u, v, sampler.get_2d());
+                    tile.add_sample(film_x, film_y, color(&r, scene, 10, &mut *sampler));
+                }
+            }
+        }
+        framebuffer.lock().unwrap().merge_tile(tile);
+        bar.inc(1);
+    });
+
+    bar.finish();
+    framebuffer.into_inner().unwrap()
+}
+
+pub fn color(r: &Ray, scene: &Scene, depth: i64, sampler: &mut dyn Sampler) -> Vec3 {
+    trace(r, scene, depth, sampler, false)
+}
+
+/// Traces one pixel with every sample and bounce logged to stderr, for
+/// hunting down fireflies without re-rendering the frame. Returns the
+/// average of the samples.
+pub fn debug_pixel(scene: &Scene, cam: &Camera, settings: &RenderSettings, x: u32, y: u32) -> Vec3 {
+    let mut sampler = make_sampler(settings.sampler, settings.samples);
+    let mut sum = Vec3::default();
+    sampler.start_pixel(x, y);
+    for s in 0..sampler.samples_per_pixel() {
+        sampler.start_sample(s);
+        let (dx, dy) = sampler.get_2d();
+        let u: Float = (x as Float + dx) / settings.width as Float;
+        let v: Float = 1.0 - (y as Float + dy) / settings.height as Float;
+        let r: Ray = cam.get_ray(u, v, sampler.get_2d());
+        eprintln!("sample {} at ({:.4}, {:.4}): origin {:?} direction {:?}", s, x as Float + dx, y as Float + dy, r.origin().e, r.direction().e);
+        let radiance = trace(&r, scene, 10, &mut *sampler, true);
+        eprintln!("sample {} radiance {:?}", s, radiance.e);
+        sum += radiance;
+    }
+    let mean = sum / sampler.samples_per_pixel() as Float;
+    eprintln!("pixel ({}, {}) mean radiance {:?}", x, y, mean.e);
+    mean
+}
+
+/// Follows one path from the camera. At every surface that is not a mirror
+/// the environment is also sampled directly with a shadow ray, and both
+/// ways of finding it are combined with multiple importance sampling.
+fn trace(r: &Ray, scene: &Scene, mut depth: i64, sampler: &mut dyn Sampler, verbose: bool) -> Vec3 {
+    let mut ray = *r;
+    let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+    let mut radiance = Vec3::default();
+    let first_depth = depth;
+    // The camera ray counts as specular: nothing else could have found what it sees
+    let mut specular_bounce = true;
+    let mut bsdf_pdf = 0.0;
+    loop {
+        if let Some(rec) = scene.world.hit(&ray, 0.0, Float::MAX) {
+            let emission = emitted(&rec.material, &rec);
+            radiance += throughput * emission;
+            let wo = -Vec3::unit_vector(&ray.direction());
+            if depth >= 50 {
+                return radiance;
+            }
+
+            if !rec.material.is_specular() {
+                let direct = sample_environment(scene, &rec, wo, sampler);
+                let lights = sample_lights(scene, &rec, wo);
+                radiance += throughput * (direct + lights);
+                if verbose {
+                    eprintln!("    environment light={:?} lights={:?}", direct.e, lights.e);
+                }
+            }
+
+            if let Some(sample) = rec.material.sample(wo, &rec, sampler) {
+                throughput = throughput * sample.f / sample.pdf;
+                if verbose {
+                    eprintln!("  bounce {}: hit t={:.6} p={:?} normal={:?} front_face={} material={:?}", depth - first_depth, rec.t, rec.p.e, Vec3::from(rec.normal).e, rec.front_face, rec.material);
+                    eprintln!("    f={:?} pdf={} specular={} throughput={:?} wi={:?}", sample.f.e, sample.pdf, sample.specular, throughput.e, sample.wi.e);
+                }
+                specular_bounce = sample.specular;
+                bsdf_pdf = sample.pdf;
+                ray = rec.spawn_ray(sample.wi);
+                depth += 1;
+            }
+            else {
+                if verbose {
+                    eprintln!("  bounce {}: hit t={:.6} p={:?} material={:?} absorbed, emitted={:?}", depth - first_depth, rec.t, rec.p.e, rec.material, emission.e);
+                }
+                return radiance;
+            }
+        }
+        else {
+            let sky = scene.environment.radiance(ray.direction());
+            let weight = if specular_bounce { 1.0 } else { power_heuristic(bsdf_pdf, scene.environment.pdf(ray.direction())) };
+            if verbose {
+                eprintln!("  bounce {}: miss, sky={:?} weight={} contribution={:?}", depth - first_depth, sky.e, weight, (throughput * sky * weight).e);
+            }
+            return radiance + throughput * sky * weight;
+        }
+    }
+}
+
+/// Light reaching `rec` from one direction picked by the environment,
+/// weighted against the BSDF having picked it instead.
+fn sample_environment(scene: &Scene, rec: &HitRecord, wo: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+    let (wi, light, light_pdf) = scene.environment.sample(sampler.get_2d());
+    if light_pdf <= 0.0 {
+        return Vec3::default();
+    }
+    let f = rec.material.eval(wo, wi, rec);
+    if f.squared_length() == 0.0 || light.squared_length() == 0.0 {
+        return Vec3::default();
+    }
+    let transmittance = scene.world.transmittance(&rec.spawn_ray(wi), 0.0, Float::MAX);
+    if transmittance == 0.0 {
+        return Vec3::default();
+    }
+    let weight = power_heuristic(light_pdf, rec.material.pdf(wo, wi, rec));
+    f * light * (transmittance * weight / light_pdf)
+}
+
+/// Light reaching `rec` straight from every light in the scene. Nothing
+/// else can find them, so they need no weighting.
+fn sample_lights(scene: &Scene, rec: &HitRecord, wo: Vec3) -> Vec3 {
+    let mut total = Vec3::default();
+    for light in &scene.lights {
+        let Some((wi, light, distance)) = light.illuminate(rec.p) else {
+            continue;
+        };
+        let f = rec.material.eval(wo, wi, rec);
+        if f.squared_length() == 0.0 || light.squared_length() == 0.0 {
+            continue;
+        }
+        let transmittance = scene.world.transmittance(&rec.spawn_ray(wi), 0.0, distance);
+        total += f * light * transmittance;
+    }
+    total
+}
+
+/// Veach's power heuristic with exponent 2, the weight of a sample taken
+/// with density `pdf` when `other_pdf` could also have produced it.
+fn power_heuristic(pdf: Float, other_pdf: Float) -> Float {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 { 0.0 } else { a / (a + b) }
+}
+
+/// Splits the window into tiles of at most `tile_size` pixels square,
+/// listed in the requested order.
+fn tiles(window: CropWindow, tile_size: u32, order: TileOrder) -> Vec<CropWindow> {
+    let width = window.x1.saturating_sub(window.x0);
+    let height = window.y1.saturating_sub(window.y0);
+    let nx = width.div_ceil(tile_size);
+    let ny = height.div_ceil(tile_size);
+
+    let grid: Vec<(u32, u32)> = match order {
+        TileOrder::Scanline => (0..ny).flat_map(|ty| (0..nx).map(move |tx| (tx, ty))).collect(),
+        TileOrder::Spiral => spiral_order(nx, ny),
+        TileOrder::Hilbert => hilbert_order(nx, ny),
+    };
+
+    grid.into_iter().map(|(tx, ty)| {
+        let x0 = window.x0 + tx * tile_size;
+        let y0 = window.y0 + ty * tile_size;
+        CropWindow::new(x0, y0, (x0 + tile_size).min(window.x1), (y0 + tile_size).min(window.y1))
+    }).collect()
+}
+
+/// Walks a square spiral out from the centre tile, keeping the tiles that
+/// fall inside the `nx` by `ny` grid.
+fn spiral_order(nx: u32, ny: u32) -> Vec<(u32, u32)> {
+    let total = (nx * ny) as usize;
+    let mut order = Vec::with_capacity(total);
+    let (mut x, mut y) = ((nx as i64 - 1) / 2, (ny as i64 - 1) / 2);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut leg = 1;
+    let mut direction = 0;
+
+    while order.len() < total {
+        // Legs grow by one every second turn: 1, 1, 2, 2, 3, 3, ...
+        for _ in 0..2 {
+            let (dx, dy) = directions[direction % 4];
+            for _ in 0..leg {
+                if x >= 0 && y >= 0 && x < nx as i64 && y < ny as i64 {
+                    order.push((x as u32, y as u32));
+                }
+                x += dx;
+                y += dy;
+            }
+            direction += 1;
+        }
+        leg += 1;
+    }
+    order
+}
+
+/// Visits the `nx` by `ny` grid along a Hilbert curve over the enclosing
+/// power of two square.
+fn hilbert_order(nx: u32, ny: u32) -> Vec<(u32, u32)> {
+    let n = nx.max(ny).max(1).next_power_of_two();
+    (0..n * n)
+        .map(|d| hilbert_d2xy(n, d))
+        .filter(|&(x, y)| x < nx && y < ny)
+        .collect()
+}
+
+fn hilbert_d2xy(n: u32, d: u32) -> (u32, u32) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = d;
+    let mut s = 1;
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [TileOrder; 3] = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert];
+
+    /// Asserts that `order` lists every tile of the `nx` by `ny` grid once.
+    fn assert_grid_permutation(order: &[(u32, u32)], nx: u32, ny: u32) {
+        let mut seen = vec![false; (nx * ny) as usize];
+        for &(x, y) in order {
+            assert!(x < nx && y < ny, "tile ({}, {}) outside {}x{}", x, y, nx, ny);
+            assert!(!seen[(y * nx + x) as usize], "tile ({}, {}) listed twice in {}x{}", x, y, nx, ny);
+            seen[(y * nx + x) as usize] = true;
+        }
+        assert_eq!(order.len(), (nx * ny) as usize, "{}x{}", nx, ny);
+    }
+
+    #[test]
+    fn spiral_and_hilbert_visit_every_tile_once() {
+        for (nx, ny) in [(1, 1), (1, 7), (7, 1), (4, 4), (5, 3), (3, 8), (13, 10)] {
+            assert_grid_permutation(&spiral_order(nx, ny), nx, ny);
+            assert_grid_permutation(&hilbert_order(nx, ny), nx, ny);
+        }
+    }
+
+    #[test]
+    fn spiral_starts_in_the_centre() {
+        assert_eq!(spiral_order(5, 3)[0], (2, 1));
+    }
+
+    #[test]
+    fn tiles_cover_each_pixel_once() {
+        let windows = [
+            CropWindow::new(0, 0, 400, 300),
+            CropWindow::new(0, 0, 7, 100),
+            CropWindow::new(13, 29, 111, 64),
+            CropWindow::new(5, 5, 6, 6),
+        ];
+        for window in windows {
+            for tile_size in [1, 16, 32, 50, 1000] {
+                for order in ORDERS {
+                    let (width, height) = (window.x1 - window.x0, window.y1 - window.y0);
+                    let mut covered = vec![0; (width * height) as usize];
+                    for tile in tiles(window, tile_size, order) {
+                        assert!(tile.x0 < tile.x1 && tile.y0 < tile.y1, "empty tile {:?}", tile);
+                        for y in tile.y0..tile.y1 {
+                            for x in tile.x0..tile.x1 {
+                                assert!(x >= window.x0 && x < window.x1 && y >= window.y0 && y < window.y1);
+                                covered[((y - window.y0) * width + x - window.x0) as usize] += 1;
+                            }
+                        }
+                    }
+                    assert!(covered.iter().all(|&c| c == 1), "{:?} size {} {:?}", window, tile_size, order);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Real roots of low degree polynomials, for intersecting rays with
+//! implicit surfaces. Roots are returned in increasing order.
+
+use crate::float::Float;
+use crate::float::consts::PI;
+
+/// Coefficients smaller than this are treated as zero.
+const EQN_EPS: Float = Float::EPSILON * 1024.0;
+
+fn is_zero(x: Float) -> bool {
+    x.abs() < EQN_EPS
+}
+
+/// Roots of `a t^2 + b t + c`, or `None` if there are no real ones. Uses the
+/// cancellation free form of the quadratic formula.
+pub fn solve_quadratic(a: Float, b: Float, c: Float) -> Option<(Float, Float)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+/// Roots of the monic cubic `t^3 + a t^2 + b t + c`, and how many of the
+/// returned slots are used (Schwarze, "Cubic and Quartic Roots", Graphics
+/// Gems I).
+pub fn solve_cubic(a: Float, b: Float, c: Float) -> ([Float; 3], usize) {
+    // Substitute t = y - a/3 to get y^3 + 3p y + 2q
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+
+    let mut roots = [0.0; 3];
+    let n = if is_zero(d) {
+        if is_zero(q) {
+            1
+        } else {
+            let u = (-q).cbrt();
+            roots[0] = 2.0 * u;
+            roots[1] = -u;
+            2
+        }
+    } else if d < 0.0 {
+        // Three real roots
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        roots[0] = t * phi.cos();
+        roots[1] = -t * (phi + PI / 3.0).cos();
+        roots[2] = -t * (phi - PI / 3.0).cos();
+        3
+    } else {
+        let sqrt_d = d.sqrt();
+        roots[0] = (sqrt_d - q).cbrt() - (sqrt_d + q).cbrt();
+        1
+    };
+
+    for root in roots.iter_mut().take(n) {
+        *root -= a / 3.0;
+    }
+    roots[..n].sort_by(Float::total_cmp);
+    (roots, n)
+}
+
+/// Roots of the monic quartic `t^4 + a t^3 + b t^2 + c t + d`, and how many
+/// of the returned slots are used. Ferrari's method loses precision for
+/// some rays, so every root is polished with a few Newton steps.
+pub fn solve_quartic(a: Float, b: Float, c: Float, d: Float) -> ([Float; 4], usize) {
+    // Substitute t = y - a/4 to get y^4 + p y^2 + q y + r
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + c;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * c / 4.0 + d;
+
+    let mut roots = [0.0; 4];
+    let mut n = 0;
+    if is_zero(r) {
+        // y (y^3 + p y + q) = 0
+        let (cubic, m) = solve_cubic(0.0, p, q);
+        roots[..m].copy_from_slice(&cubic[..m]);
+        roots[m] = 0.0;
+        n = m + 1;
+    } else {
+        // One root of the resolvent cubic splits the quartic into two quadratics
+        let (cubic, _) = solve_cubic(-p / 2.0, -r, r * p / 2.0 - q * q / 8.0);
+        let z = cubic[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if is_zero(u) { 0.0 } else if u > 0.0 { u.sqrt() } else { return (roots, 0) };
+        let v = if is_zero(v) { 0.0 } else if v > 0.0 { v.sqrt() } else { return (roots, 0) };
+        let v = if q < 0.0 { -v } else { v };
+
+        for (b, c) in [(v, z - u), (-v, z + u)] {
+            if let Some((y0, y1)) = solve_quadratic(1.0, b, c) {
+                roots[n] = y0;
+                roots[n + 1] = y1;
+                n += 2;
+            }
+        }
+    }
+
+    for root in roots.iter_mut().take(n) {
+        *root -= a / 4.0;
+        for _ in 0..2 {
+            let t = *root;
+            let f = (((t + a) * t + b) * t + c) * t + d;
+            let df = ((4.0 * t + 3.0 * a) * t + 2.0 * b) * t + c;
+            if df != 0.0 {
+                *root = t - f / df;
+            }
+        }
+    }
+    roots[..n].sort_by(Float::total_cmp);
+    (roots, n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::material::Material;
+    use crate::ray::Ray;
+    use crate::torus::Torus;
+    use crate::vec3::Vec3;
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-7;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-3;
+
+    fn assert_roots(found: &[Float], expected: &[Float]) {
+        assert_eq!(found.len(), expected.len(), "{:?} != {:?}", found, expected);
+        for (f, e) in found.iter().zip(expected) {
+            assert!((f - e).abs() <= TOLERANCE * e.abs().max(1.0), "{:?} != {:?}", found, expected);
+        }
+    }
+
+    #[test]
+    fn quadratic_known_roots() {
+        let (t0, t1) = solve_quadratic(1.0, -4.0, 3.0).unwrap();
+        assert_roots(&[t0, t1], &[1.0, 3.0]);
+        let (t0, t1) = solve_quadratic(-2.0, 0.0, 8.0).unwrap();
+        assert_roots(&[t0, t1], &[-2.0, 2.0]);
+        assert!(solve_quadratic(1.0, 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn quadratic_double_root() {
+        let (t0, t1) = solve_quadratic(1.0, -4.0, 4.0).unwrap();
+        assert_roots(&[t0, t1], &[2.0, 2.0]);
+    }
+
+    #[test]
+    fn quadratic_degenerate_leading_coefficient() {
+        // Linear, then nothing at all
+        let (t0, t1) = solve_quadratic(0.0, 2.0, -4.0).unwrap();
+        assert_roots(&[t0, t1], &[2.0, 2.0]);
+        assert!(solve_quadratic(0.0, 0.0, 1.0).is_none());
+
+        // Nearly linear: the small root stays accurate and the other runs off
+        let (t0, t1) = solve_quadratic(1e-12, 1.0, -1.0).unwrap();
+        assert!(t0 < -1e11);
+        assert_roots(&[t1], &[1.0]);
+    }
+
+    #[test]
+    fn quadratic_avoids_cancellation() {
+        // The textbook formula loses every digit of the small root here
+        let (t0, t1) = solve_quadratic(1.0, 1e8, 1.0).unwrap();
+        assert_roots(&[t0], &[-1e8]);
+        assert!((t1 + 1e-8).abs() < 1e-8 * TOLERANCE, "{}", t1);
+    }
+
+    #[test]
+    fn cubic_known_roots() {
+        let (roots, n) = solve_cubic(-6.0, 11.0, -6.0);
+        assert_roots(&roots[..n], &[1.0, 2.0, 3.0]);
+        // (t - 1)(t^2 + 1) has a single real root
+        let (roots, n) = solve_cubic(-1.0, 1.0, -1.0);
+        assert_roots(&roots[..n], &[1.0]);
+    }
+
+    #[test]
+    fn cubic_repeated_roots() {
+        // (t - 1)^2 (t - 3) lists the double root once
+        let (roots, n) = solve_cubic(-5.0, 7.0, -3.0);
+        assert_roots(&roots[..n], &[1.0, 3.0]);
+        let (roots, n) = solve_cubic(-6.0, 12.0, -8.0);
+        assert_roots(&roots[..n], &[2.0]);
+    }
+
+    #[test]
+    fn quartic_known_roots() {
+        let (roots, n) = solve_quartic(-10.0, 35.0, -50.0, 24.0);
+        assert_roots(&roots[..n], &[1.0, 2.0, 3.0, 4.0]);
+        // (t - 1)(t + 2)(t^2 + 1)
+        let (roots, n) = solve_quartic(1.0, -1.0, 1.0, -2.0);
+        assert_roots(&roots[..n], &[-2.0, 1.0]);
+        // (t^2 + 1)(t^2 + 4)
+        let (_, n) = solve_quartic(0.0, 5.0, 0.0, 4.0);
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn quartic_zero_constant_term() {
+        // t (t + 3)(t - 1)(t - 2)
+        let (roots, n) = solve_quartic(0.0, -7.0, 6.0, 0.0);
+        assert_roots(&roots[..n], &[-3.0, 0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn quartic_double_roots() {
+        // (t - 1)^2 (t - 3)^2
+        let (roots, n) = solve_quartic(-8.0, 22.0, -24.0, 9.0);
+        assert_roots(&roots[..n], &[1.0, 1.0, 3.0, 3.0]);
+    }
+
+    #[test]
+    fn quartic_for_ray_tangent_to_torus() {
+        // A ray along x at the height of the top of the tube of a torus with
+        // radii 1 and 1/4 touches it at x = -1 and x = 1. From x = -3 that is
+        // ((t - 2)(t - 4))^2 = 0
+        let (roots, n) = solve_quartic(-12.0, 52.0, -96.0, 64.0);
+        assert_roots(&roots[..n], &[2.0, 2.0, 4.0, 4.0]);
+
+        let torus = Torus::new(Vec3::default(), 1.0, 0.25, Material::default());
+        let ray = |y: Float| Ray::new(Vec3::new(-3.0, y, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = torus.hit(&ray(0.25), 0.0, Float::MAX).expect("a tangent ray grazes the torus");
+        assert!((rec.t - 2.0).abs() < 1e-2, "{}", rec.t);
+        // Just below it cuts through the tube, just above it misses
+        let rec = torus.hit(&ray(0.249), 0.0, Float::MAX).expect("the ray cuts the tube");
+        assert!((rec.t - 2.0).abs() < 0.1, "{}", rec.t);
+        assert!(torus.hit(&ray(0.251), 0.0, Float::MAX).is_none());
+    }
+}
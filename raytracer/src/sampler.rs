@@ -0,0 +1,682 @@
+use std::sync::OnceLock;
+
+use rand::Rng;
+
+use crate::float::{consts, Float, ONE_MINUS_EPSILON};
+use crate::vec3::Vec3;
+
+/// Source of sample values for one pixel at a time.
+///
+/// A renderer calls `start_pixel` once per pixel and `start_sample` before
+/// every sample in it; each `get_1d`/`get_2d` call then consumes the next
+/// dimension of that sample. Pixel position, lens position and every bounce
+/// of the BSDF all draw from here, so a well distributed sampler converges
+/// faster than independent random numbers at the same sample count.
+pub trait Sampler: Send + Sync {
+    fn start_pixel(&mut self, x: u32, y: u32);
+    fn start_sample(&mut self, index: u32);
+    fn get_1d(&mut self) -> Float;
+    fn get_2d(&mut self) -> (Float, Float);
+    fn samples_per_pixel(&self) -> u32;
+    fn clone_box(&self) -> Box<dyn Sampler>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplerKind {
+    Random,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+impl SamplerKind {
+    pub fn from_name(name: &str) -> Option<SamplerKind> {
+        match name {
+            "random" => Some(SamplerKind::Random),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            "bluenoise" | "blue-noise" => Some(SamplerKind::BlueNoise),
+            _ => None,
+        }
+    }
+}
+
+pub fn make_sampler(kind: SamplerKind, samples_per_pixel: u32) -> Box<dyn Sampler> {
+    match kind {
+        SamplerKind::Random => Box::new(RandomSampler::random(samples_per_pixel)),
+        SamplerKind::Stratified => Box::new(StratifiedSampler::stratified(samples_per_pixel, true)),
+        SamplerKind::Halton => Box::new(HaltonSampler::halton(samples_per_pixel)),
+        SamplerKind::Sobol => Box::new(SobolSampler::sobol(samples_per_pixel)),
+        SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::blue_noise(samples_per_pixel)),
+    }
+}
+
+/// Position inside the sample stream shared by all samplers.
+#[derive(Debug, Clone, Copy, Default)]
+struct SampleState {
+    pixel_seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl SampleState {
+    fn start_pixel(&mut self, x: u32, y: u32) {
+        self.pixel = (x, y);
+        self.pixel_seed = hash(x as u64, y as u64);
+        self.index = 0;
+        self.dimension = 0;
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    /// Returns a seed unique to the current pixel and dimension, and moves
+    /// on to the next dimension.
+    fn next_dimension(&mut self, consumed: u32) -> u64 {
+        let seed = hash(self.pixel_seed, self.dimension as u64);
+        self.dimension += consumed;
+        seed
+    }
+}
+
+/// Independent uniform random numbers, the behaviour before samplers existed.
+#[derive(Debug, Clone)]
+pub struct RandomSampler {
+    samples_per_pixel: u32,
+}
+
+impl RandomSampler {
+    pub fn random(samples_per_pixel: u32) -> RandomSampler {
+        RandomSampler { samples_per_pixel }
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn start_pixel(&mut self, _x: u32, _y: u32) {}
+
+    fn start_sample(&mut self, _index: u32) {}
+
+    fn get_1d(&mut self) -> Float {
+        rand::random::<Float>()
+    }
+
+    fn get_2d(&mut self) -> (Float, Float) {
+        let mut rng = rand::thread_rng();
+        (rng.gen::<Float>(), rng.gen::<Float>())
+    }
+
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn clone_box(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+/// Splits every dimension into `samples_per_pixel` strata (a grid for 2D)
+/// and visits them in a per-pixel, per-dimension shuffled order, optionally
+/// jittering inside each stratum. The 2D grid is as square as the sample
+/// count allows while still having exactly one cell per sample.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    jitter: bool,
+    state: SampleState,
+}
+
+impl StratifiedSampler {
+    pub fn stratified(samples_per_pixel: u32, jitter: bool) -> StratifiedSampler {
+        StratifiedSampler {
+            samples_per_pixel: samples_per_pixel.max(1),
+            jitter,
+            state: SampleState::default(),
+        }
+    }
+
+    /// Position inside the stratum along `axis`, the same every time the
+    /// same pixel, sample and dimension come round.
+    fn offset(&self, seed: u64, axis: u64) -> Float {
+        if self.jitter {
+            to_unit_float((hash(hash(seed, self.state.index as u64), axis) >> 32) as u32)
+        } else {
+            0.5
+        }
+    }
+}
+
+/// Columns and rows of a grid with exactly `n` cells, as close to square as
+/// the divisors of `n` allow.
+fn stratum_grid(n: u32) -> (u32, u32) {
+    let nx = (1..=(n as Float).sqrt() as u32).rev().find(|&nx| n.is_multiple_of(nx)).unwrap_or(1);
+    (nx, n / nx)
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel(&mut self, x: u32, y: u32) {
+        self.state.start_pixel(x, y);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.state.start_sample(index);
+    }
+
+    fn get_1d(&mut self) -> Float {
+        let seed = self.state.next_dimension(1);
+        let n = self.samples_per_pixel;
+        let stratum = permute(self.state.index % n, n, seed as u32);
+        ((stratum as Float + self.offset(seed, 0)) / n as Float).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (Float, Float) {
+        let seed = self.state.next_dimension(2);
+        let n = self.samples_per_pixel;
+        let (nx, ny) = stratum_grid(n);
+        let stratum = permute(self.state.index % n, n, seed as u32);
+        let x = (stratum % nx) as Float + self.offset(seed, 0);
+        let y = (stratum / nx) as Float + self.offset(seed, 1);
+        ((x / nx as Float).min(ONE_MINUS_EPSILON), (y / ny as Float).min(ONE_MINUS_EPSILON))
+    }
+
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn clone_box(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Halton sequence with one prime base per dimension, decorrelated between
+/// pixels by a Cranley-Patterson rotation. Dimensions past the prime table
+/// fall back to hashed, uncorrelated values.
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    samples_per_pixel: u32,
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    pub fn halton(samples_per_pixel: u32) -> HaltonSampler {
+        HaltonSampler {
+            samples_per_pixel,
+            state: SampleState::default(),
+        }
+    }
+
+    fn sample_dimension(&self, dimension: u32, seed: u64) -> Float {
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => {
+                let rotation = to_unit_float(hash(seed, base as u64) as u32);
+                (radical_inverse(base, self.state.index as u64) + rotation).fract()
+            }
+            None => to_unit_float((hash(hash(seed, dimension as u64), self.state.index as u64) >> 32) as u32),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel(&mut self, x: u32, y: u32) {
+        self.state.start_pixel(x, y);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.state.start_sample(index);
+    }
+
+    fn get_1d(&mut self) -> Float {
+        let dimension = self.state.dimension;
+        let seed = self.state.next_dimension(1);
+        self.sample_dimension(dimension, seed)
+    }
+
+    fn get_2d(&mut self) -> (Float, Float) {
+        let dimension = self.state.dimension;
+        let seed = self.state.next_dimension(2);
+        (self.sample_dimension(dimension, seed), self.sample_dimension(dimension + 1, seed))
+    }
+
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn clone_box(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+/// Padded Sobol sampler: every pair of dimensions is the (0,2)-sequence made
+/// of the first two Sobol dimensions, with its own random digit scramble and
+/// sample order so that pairs do not correlate with each other. Converges
+/// best when the sample count is a power of two.
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    samples_per_pixel: u32,
+    state: SampleState,
+}
+
+impl SobolSampler {
+    pub fn sobol(samples_per_pixel: u32) -> SobolSampler {
+        SobolSampler {
+            samples_per_pixel: samples_per_pixel.max(1),
+            state: SampleState::default(),
+        }
+    }
+
+    fn shuffled_index(&self, seed: u64) -> u32 {
+        permute(self.state.index % self.samples_per_pixel, self.samples_per_pixel, seed as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel(&mut self, x: u32, y: u32) {
+        self.state.start_pixel(x, y);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.state.start_sample(index);
+    }
+
+    fn get_1d(&mut self) -> Float {
+        let seed = self.state.next_dimension(1);
+        let i = self.shuffled_index(seed);
+        to_unit_float(i.reverse_bits() ^ (seed >> 32) as u32)
+    }
+
+    fn get_2d(&mut self) -> (Float, Float) {
+        let seed = self.state.next_dimension(2);
+        let i = self.shuffled_index(seed);
+        let scramble = hash(seed, 0x5eed);
+        let x = i.reverse_bits() ^ scramble as u32;
+        let y = sobol_second_dimension(i) ^ (scramble >> 32) as u32;
+        (to_unit_float(x), to_unit_float(y))
+    }
+
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn clone_box(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+const BLUE_NOISE_SIZE: usize = 32;
+
+/// Blue-noise dithered sampling: every pixel walks the same Kronecker
+/// (golden ratio / R2) sequence, toroidally shifted by a blue-noise mask so
+/// that the error left in neighbouring pixels is decorrelated and looks like
+/// fine grain instead of clumps.
+#[derive(Debug, Clone)]
+pub struct BlueNoiseSampler {
+    samples_per_pixel: u32,
+    state: SampleState,
+}
+
+impl BlueNoiseSampler {
+    pub fn blue_noise(samples_per_pixel: u32) -> BlueNoiseSampler {
+        BlueNoiseSampler {
+            samples_per_pixel,
+            state: SampleState::default(),
+        }
+    }
+
+    fn shift(&self, seed: u64) -> Float {
+        let (x, y) = self.state.pixel;
+        let dx = (seed as usize) % BLUE_NOISE_SIZE;
+        let dy = ((seed >> 16) as usize) % BLUE_NOISE_SIZE;
+        let mask = blue_noise_mask();
+        mask[((y as usize + dy) % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + (x as usize + dx) % BLUE_NOISE_SIZE]
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel(&mut self, x: u32, y: u32) {
+        self.state.start_pixel(x, y);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.state.start_sample(index);
+    }
+
+    fn get_1d(&mut self) -> Float {
+        // Only the dimension may pick the mask offset: the mask itself is
+        // what decorrelates pixels.
+        let seed = hash(self.state.dimension as u64, 0);
+        self.state.next_dimension(1);
+        let golden = 0.618_033_988_749_895_f64 as Float;
+        (self.shift(seed) + self.state.index as Float * golden).fract()
+    }
+
+    fn get_2d(&mut self) -> (Float, Float) {
+        let seed = hash(self.state.dimension as u64, 0);
+        self.state.next_dimension(2);
+        // R2 sequence, the 2D generalisation of the golden ratio.
+        let g = 1.324_717_957_244_746_f64 as Float;
+        let i = self.state.index as Float;
+        (
+            (self.shift(seed) + i / g).fract(),
+            (self.shift(seed >> 32) + i / (g * g)).fract(),
+        )
+    }
+
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn clone_box(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+/// Tileable blue-noise mask with values in (0, 1), built once by repeatedly
+/// placing the next rank in the largest void (lowest Gaussian energy).
+fn blue_noise_mask() -> &'static [Float] {
+    static MASK: OnceLock<Vec<Float>> = OnceLock::new();
+    MASK.get_or_init(|| {
+        let n = BLUE_NOISE_SIZE;
+        let sigma: Float = 1.5;
+        let radius = 5i64;
+        let mut energy = vec![0.0; n * n];
+        let mut rank = vec![usize::MAX; n * n];
+
+        for r in 0..n * n {
+            let mut best = 0;
+            let mut best_energy = Float::MAX;
+            for (i, e) in energy.iter().enumerate() {
+                if rank[i] == usize::MAX && *e < best_energy {
+                    best = i;
+                    best_energy = *e;
+                }
+            }
+            rank[best] = r;
+
+            let (bx, by) = ((best % n) as i64, (best / n) as i64);
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let x = (bx + dx).rem_euclid(n as i64) as usize;
+                    let y = (by + dy).rem_euclid(n as i64) as usize;
+                    let d2 = (dx * dx + dy * dy) as Float;
+                    energy[y * n + x] += (-d2 / (2.0 * sigma * sigma)).exp();
+                }
+            }
+        }
+
+        rank.iter().map(|&r| (r as Float + 0.5) / (n * n) as Float).collect()
+    })
+}
+
+fn radical_inverse(base: u32, mut index: u64) -> Float {
+    let inv_base = 1.0 / base as Float;
+    let mut inv_base_n = 1.0;
+    let mut reversed = 0u64;
+    while index > 0 {
+        let next = index / base as u64;
+        reversed = reversed * base as u64 + (index - next * base as u64);
+        inv_base_n *= inv_base;
+        index = next;
+    }
+    (reversed as Float * inv_base_n).min(ONE_MINUS_EPSILON)
+}
+
+/// Second dimension of the Sobol sequence (primitive polynomial x + 1).
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut v = 1u32 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+pub(crate) fn to_unit_float(bits: u32) -> Float {
+    (bits as Float / 4_294_967_296.0).min(ONE_MINUS_EPSILON)
+}
+
+/// Mixes two values into a well distributed 64-bit hash (splitmix64 finalizer).
+pub(crate) fn hash(a: u64, b: u64) -> u64 {
+    let mut z = a.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ b.wrapping_add(0x632b_e59b_d9b4_e019);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Returns element `i` of a pseudo-random permutation of `0..n` chosen by
+/// `seed` (Kensler, "Correlated Multi-Jittered Sampling").
+fn permute(mut i: u32, n: u32, seed: u32) -> u32 {
+    if n <= 1 {
+        return 0;
+    }
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % n
+}
+
+/// Maps a uniform 2D sample to a point in the unit disk (z = 0) with
+/// Shirley's concentric mapping, which keeps strata intact.
+pub fn sample_unit_disk(u: (Float, Float)) -> Vec3 {
+    let (ox, oy) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if ox == 0.0 && oy == 0.0 {
+        return Vec3::default();
+    }
+    let (r, theta) = if ox.abs() > oy.abs() {
+        (ox, consts::FRAC_PI_4 * (oy / ox))
+    } else {
+        (oy, consts::FRAC_PI_2 - consts::FRAC_PI_4 * (ox / oy))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+/// Maps a uniform 2D sample to a direction uniformly distributed on the unit sphere.
+pub fn sample_unit_sphere(u: (Float, Float)) -> Vec3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * consts::PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Maps a 2D sample for the direction and a 1D sample for the radius to a
+/// point uniformly distributed inside the unit ball.
+pub fn sample_unit_ball(u: (Float, Float), r: Float) -> Vec3 {
+    r.cbrt() * sample_unit_sphere(u)
+}
+
+/// Maps a uniform 2D sample to a direction in the hemisphere around +z with
+/// density `cos(theta) / pi`, by lifting a disk sample (Malley's method).
+pub fn sample_cosine_hemisphere(u: (Float, Float)) -> Vec3 {
+    let d = sample_unit_disk(u);
+    let z = (1.0 - d.x() * d.x() - d.y() * d.y()).max(0.0).sqrt();
+    Vec3::new(d.x(), d.y(), z)
+}
+
+/// Maps a uniform 2D sample to a direction uniformly distributed over the cone
+/// around +z of directions with `cos(theta) >= cos_max`.
+pub fn sample_cone(u: (Float, Float), cos_max: Float) -> Vec3 {
+    let z = 1.0 - u.0 * (1.0 - cos_max);
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * consts::PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const CASES: usize = 1000;
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-12;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-6;
+
+    #[test]
+    fn radical_inverse_known_values() {
+        let base2 = [(1, 0.5), (2, 0.25), (3, 0.75), (4, 0.125), (6, 0.375), (7, 0.875)];
+        for (index, expected) in base2 {
+            assert!((radical_inverse(2, index) - expected).abs() < TOLERANCE, "base 2 index {}", index);
+        }
+        let base3 = [(1, 1.0 / 3.0), (2, 2.0 / 3.0), (3, 1.0 / 9.0), (4, 4.0 / 9.0), (5, 7.0 / 9.0)];
+        for (index, expected) in base3 {
+            assert!((radical_inverse(3, index) - expected).abs() < TOLERANCE, "base 3 index {}", index);
+        }
+        assert_eq!(radical_inverse(5, 0), 0.0);
+    }
+
+    #[test]
+    fn sobol_second_dimension_known_values() {
+        let expected = [0.0, 0.5, 0.75, 0.25, 0.625, 0.125, 0.375, 0.875];
+        for (i, x) in expected.iter().enumerate() {
+            assert_eq!(to_unit_float(sobol_second_dimension(i as u32)), *x);
+        }
+    }
+
+    #[test]
+    fn sobol_pairs_fill_every_elementary_interval() {
+        // The first 16 points of a (0,2)-sequence put one point in each box
+        // of every 16-box split of the unit square
+        for log_x in 0..=4 {
+            let (nx, ny) = (1u32 << log_x, 1u32 << (4 - log_x));
+            let mut seen = [false; 16];
+            for i in 0..16u32 {
+                let x = to_unit_float(i.reverse_bits());
+                let y = to_unit_float(sobol_second_dimension(i));
+                let cell = (y * ny as Float) as usize * nx as usize + (x * nx as Float) as usize;
+                assert!(!seen[cell], "{}x{} cell {} hit twice", nx, ny, cell);
+                seen[cell] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn permute_is_a_bijection() {
+        for n in [1, 2, 3, 7, 8, 100, 1000] {
+            for seed in [0, 1, 0xdead_beef, 0x1234_5678] {
+                let mut seen = vec![false; n as usize];
+                for i in 0..n {
+                    let p = permute(i, n, seed);
+                    assert!(p < n && !seen[p as usize], "n {} seed {} maps twice to {}", n, seed, p);
+                    seen[p as usize] = true;
+                }
+            }
+        }
+    }
+
+    /// Runs a whole pixel of `samples` through a fresh stratified sampler
+    /// and counts how many samples land in each stratum of the first 1D and
+    /// the first 2D dimension.
+    fn stratum_counts(samples: u32) -> (Vec<u32>, Vec<u32>) {
+        let mut sampler = StratifiedSampler::stratified(samples, true);
+        let (nx, ny) = stratum_grid(samples);
+        let (mut counts_1d, mut counts_2d) = (vec![0; samples as usize], vec![0; samples as usize]);
+        sampler.start_pixel(3, 5);
+        for s in 0..samples {
+            sampler.start_sample(s);
+            let x = sampler.get_1d();
+            counts_1d[(x * samples as Float) as usize] += 1;
+            let (u, v) = sampler.get_2d();
+            counts_2d[(v * ny as Float) as usize * nx as usize + (u * nx as Float) as usize] += 1;
+        }
+        (counts_1d, counts_2d)
+    }
+
+    /// Sample counts to check stratification for. With thousands of strata
+    /// f32 cannot always keep a jittered sample off its neighbour's edge.
+    #[cfg(not(feature = "f32"))]
+    const STRATA_COUNTS: [u32; 6] = [1, 7, 16, 24, 90, 8000];
+    #[cfg(feature = "f32")]
+    const STRATA_COUNTS: [u32; 5] = [1, 7, 16, 24, 90];
+
+    #[test]
+    fn every_stratum_gets_one_sample() {
+        for samples in STRATA_COUNTS {
+            let (nx, ny) = stratum_grid(samples);
+            assert_eq!(nx * ny, samples);
+            let (counts_1d, counts_2d) = stratum_counts(samples);
+            assert!(counts_1d.iter().all(|&c| c == 1), "1D strata missed for {} samples", samples);
+            assert!(counts_2d.iter().all(|&c| c == 1), "2D strata missed for {} samples", samples);
+        }
+        assert_eq!(stratum_grid(8000), (80, 100));
+    }
+
+    #[test]
+    fn samplers_repeat_for_the_same_pixel() {
+        let kinds = [SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol, SamplerKind::BlueNoise];
+        for kind in kinds {
+            let values = || {
+                let mut sampler = make_sampler(kind, 16);
+                sampler.start_pixel(7, 11);
+                sampler.start_sample(5);
+                // Well past Halton's prime table
+                (0..40).map(|_| sampler.get_2d()).collect::<Vec<_>>()
+            };
+            assert_eq!(values(), values(), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn warps_stay_in_their_domain() {
+        let mut rng = StdRng::seed_from_u64(26);
+        let slack = 1e-5;
+        for _ in 0..CASES {
+            let u = (rng.gen::<Float>(), rng.gen::<Float>());
+
+            let disk = sample_unit_disk(u);
+            assert!(disk.length() <= 1.0 + slack && disk.z() == 0.0);
+
+            let sphere = sample_unit_sphere(u);
+            assert!((sphere.length() - 1.0).abs() < slack);
+
+            let ball = sample_unit_ball(u, rng.gen());
+            assert!(ball.length() <= 1.0 + slack);
+
+            let hemisphere = sample_cosine_hemisphere(u);
+            assert!((hemisphere.length() - 1.0).abs() < slack && hemisphere.z() >= 0.0);
+
+            let cos_max = rng.gen::<Float>() * 2.0 - 1.0;
+            let cone = sample_cone(u, cos_max);
+            assert!((cone.length() - 1.0).abs() < slack && cone.z() >= cos_max - slack);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use crate::environment::Environment;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::light::Light;
+
+/// Everything that is rendered: the objects and, later on, the lights and
+/// background that go with them.
+pub struct Scene {
+    pub world: HittableList,
+    /// What rays see when they leave the scene, and light it with.
+    pub environment: Environment,
+    /// Point, spot and directional lights, found only with shadow rays.
+    pub lights: Vec<Light>,
+}
+
+impl Scene {
+    /// Scene under the default sky.
+    pub fn new(world: HittableList) -> Scene {
+        Scene { world, environment: Environment::default(), lights: Vec::new() }
+    }
+
+    pub fn with_environment(self, environment: Environment) -> Scene {
+        Scene { environment, ..self }
+    }
+
+    pub fn add(&mut self, object: Box<dyn Hittable>) {
+        self.world.add(object);
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+}
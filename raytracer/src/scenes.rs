@@ -0,0 +1,431 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::float::Float;
+use crate::aabb::Aabb;
+use crate::camera::Camera;
+use crate::capsule::Capsule;
+use crate::cone::Cone;
+use crate::csg::Csg;
+use crate::cube::Cube;
+use crate::cylinder::Cylinder;
+use crate::environment::Environment;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::light::Light;
+use crate::material::Material;
+use crate::matrix::Mat4;
+use crate::medium::{ConstantMedium, Density, HeterogeneousMedium};
+use crate::normal_map::{NormalMap, NormalMapped};
+use crate::plane::Plane;
+use crate::principled::Principled;
+use crate::scene::Scene;
+use crate::sdf::{Sdf, SdfObject};
+use crate::sky::Sky;
+use crate::sphere::Sphere;
+use crate::texture::{Image, Texture};
+use crate::torus::Torus;
+use crate::transform::Transform;
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+
+/// Looks up one of the built-in scenes together with the camera it is meant
+/// to be viewed from.
+pub fn by_name(name: &str, aspect_ratio: Float) -> Option<(Scene, Camera)> {
+    match name {
+        "cornell" => Some((cornell_box(), Camera::new(
+            Vec3::new(40.0, 20.0, 0.0),
+            Vec3::new(0.0, 20.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            aspect_ratio,
+        ))),
+        "random" => Some((random_scene(), Camera::new(
+            Vec3::new(13.0, 2.0, 3.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            20.0,
+            aspect_ratio,
+        ))),
+        "plane" => Some((plane_scene(), Camera::new(
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            aspect_ratio,
+        ))),
+        "shapes" => Some((shapes_scene(), Camera::new(
+            Vec3::new(0.0, 4.0, 12.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            aspect_ratio,
+        ))),
+        "csg" => Some((csg_scene(), Camera::new(
+            Vec3::new(0.0, 4.0, 12.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            aspect_ratio,
+        ))),
+        "sdf" => Some((sdf_scene(), Camera::new(
+            Vec3::new(0.0, 4.0, 12.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            aspect_ratio,
+        ))),
+        "clouds" => Some((clouds_scene(), Camera::new(
+            Vec3::new(0.0, 2.0, 14.0),
+            Vec3::new(0.0, 3.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            50.0,
+            aspect_ratio,
+        ))),
+        "materials" => Some((materials_scene(), Camera::new(
+            Vec3::new(0.0, 6.0, 12.0),
+            Vec3::new(0.0, 0.5, -1.5),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            aspect_ratio,
+        ))),
+        "detail" => Some((detail_scene(), Camera::new(
+            Vec3::new(0.0, 3.0, 12.0),
+            Vec3::new(0.0, 1.2, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            aspect_ratio,
+        ))),
+        "daylight" => Some((daylight_scene(), Camera::new(
+            Vec3::new(0.0, 4.0, 12.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            aspect_ratio,
+        ))),
+        "studio" => Some((studio_scene(), Camera::new(
+            Vec3::new(0.0, 4.0, 12.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            aspect_ratio,
+        ))),
+        _ => None,
+    }
+}
+
+pub fn random_scene() -> Scene {
+    let mut rng = rand::thread_rng();
+
+    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
+
+    list.push(Box::new(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Material::Lambertian { albedo: Vec3::new(0.5, 0.5, 0.5) })
+    ));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat: Float = rng.gen::<Float>();
+            let center: Vec3 = Vec3::new(a as Float + 0.9 * rng.gen::<Float>(),
+                0.2, b as Float + 0.9 * rng.gen::<Float>());
+            if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                if choose_mat < 0.8 {  // diffuse
+                    list.push(Box::new(Sphere::new(
+                        center,
+                        0.2,
+                        Material::Lambertian { albedo: Vec3::random() })
+                    ));
+                } else {  //metal
+                    list.push(Box::new(Sphere::new(
+                        center,
+                        0.2,
+                        Material::Metal {
+                            albedo: Vec3::new(
+                                0.5 * (1.0 + rng.gen::<Float>()),
+                                0.5 * (1.0 + rng.gen::<Float>()),
+                                0.5 * (1.0 + rng.gen::<Float>())),
+                            fuzz: 0.5 * rng.gen::<Float>() })
+                    ));
+                }
+            }
+
+            list.push(Box::new(Sphere::new(
+                Vec3::new(-4.0, 1.0, 0.0),
+                1.0,
+                Material::Lambertian { albedo: Vec3::new(0.4, 0.4, 0.1) })
+            ));
+
+            list.push(Box::new(Sphere::new(
+                Vec3::new(4.0, 1.0, 0.0),
+                1.0,
+                Material::Metal { albedo: Vec3::new(0.7, 0.6, 0.5), fuzz: 0.0 })
+            ));
+        }
+    }
+    Scene::new(HittableList::new(list))
+}
+
+pub fn plane_scene() -> Scene {
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(
+            Vec3::new(0.0, 0.0, -1.0),
+            0.5,
+            Material::Lambertian { albedo: Vec3::new(0.8, 0.8, 0.3) },
+        )),
+        Box::new(Sphere::new(
+            Vec3::new(0.0, -100.5, -1.0),
+            100.0,
+            Material::Lambertian { albedo: Vec3::new(0.8, 0.8, 0.0) },
+        )),
+        Box::new(Plane::new(
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            Material::Lambertian { albedo: Vec3::new(0.8, 0.8, 0.0) },
+        )),
+    ];
+    Scene::new(HittableList::new(list))
+}
+
+/// One of each analytic primitive on a ground plane.
+pub fn shapes_scene() -> Scene {
+    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
+    let ground = Material::Lambertian { albedo: Vec3::new(0.5, 0.5, 0.5) };
+
+    list.push(Box::new(Plane::new(Vec3::new(0.0, 1.0, 0.0), 0.0, ground)));
+    list.push(Box::new(Cylinder::new(
+        Vec3::new(-4.5, 0.0, 0.0),
+        1.0,
+        2.0,
+        Material::Lambertian { albedo: Vec3::new(0.7, 0.2, 0.2) },
+    )));
+    list.push(Box::new(Cone::new(
+        Vec3::new(-1.5, 0.0, 0.0),
+        1.0,
+        2.5,
+        Material::Lambertian { albedo: Vec3::new(0.2, 0.6, 0.2) },
+    )));
+    let torus = Torus::new(Vec3::default(), 1.0, 0.35, Material::Metal { albedo: Vec3::new(0.8, 0.7, 0.4), fuzz: 0.1 });
+    list.push(Box::new(Transform::rotate(Box::new(torus), Vec3::new(1.0, 0.0, 0.0), 60.0).then(Mat4::translation(Vec3::new(1.5, 1.4, 0.0)))));
+    list.push(Box::new(Capsule::new(
+        Vec3::new(4.5, 0.7, 0.0),
+        0.7,
+        1.5,
+        Material::Lambertian { albedo: Vec3::new(0.2, 0.3, 0.7) },
+    )));
+    Scene::new(HittableList::new(list))
+}
+
+/// The primitives outdoors in the late afternoon, with long shadows.
+pub fn daylight_scene() -> Scene {
+    let sun = Sky::sun_direction(25.0, 235.0);
+    shapes_scene().with_environment(Environment::Sky(Arc::new(Sky::new(sun, 3.0))))
+}
+
+/// The primitives in a dark room, lit by a spot from the front left, a dim
+/// point light on the right and a directional light from behind.
+pub fn studio_scene() -> Scene {
+    let mut scene = shapes_scene().with_environment(Environment::Constant(Vec3::new(0.02, 0.02, 0.02)));
+    scene.add_light(Light::spot(Vec3::new(-6.0, 8.0, 8.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(300.0, 280.0, 250.0), 50.0, 10.0));
+    scene.add_light(Light::point(Vec3::new(7.0, 3.0, 4.0), Vec3::new(10.0, 12.0, 16.0)));
+    scene.add_light(Light::directional(Vec3::new(0.3, -0.5, 1.0), Vec3::new(0.6, 0.6, 0.7)));
+    scene
+}
+
+/// A box with a ball carved out of it, a lens made of two intersected
+/// spheres, and a cylinder joined with a sphere.
+pub fn csg_scene() -> Scene {
+    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
+    let ground = Material::Lambertian { albedo: Vec3::new(0.5, 0.5, 0.5) };
+    let red = Material::Lambertian { albedo: Vec3::new(0.7, 0.2, 0.2) };
+    let green = Material::Lambertian { albedo: Vec3::new(0.2, 0.6, 0.2) };
+    let blue = Material::Lambertian { albedo: Vec3::new(0.2, 0.3, 0.7) };
+
+    list.push(Box::new(Plane::new(Vec3::new(0.0, 1.0, 0.0), 0.0, ground)));
+
+    let cube = Cube::new(Vec3::new(-5.0, 0.0, -1.0), Vec3::new(-3.0, 2.0, 1.0), red.clone());
+    let hole = Sphere::new(Vec3::new(-4.0, 2.0, 1.0), 1.3, red);
+    list.push(Box::new(Csg::difference(Box::new(cube), Box::new(hole))));
+
+    let front = Sphere::new(Vec3::new(0.0, 1.2, -1.6), 2.0, green.clone());
+    let back = Sphere::new(Vec3::new(0.0, 1.2, 1.6), 2.0, green);
+    list.push(Box::new(Csg::intersection(Box::new(front), Box::new(back))));
+
+    let post = Cylinder::new(Vec3::new(4.0, 0.0, 0.0), 0.5, 2.0, blue.clone());
+    let ball = Sphere::new(Vec3::new(4.0, 2.0, 0.0), 0.9, blue);
+    list.push(Box::new(Csg::union(Box::new(post), Box::new(ball))));
+    Scene::new(HittableList::new(list))
+}
+
+/// Distance field shapes: a blob of smoothly joined balls, a rounded box
+/// with a smooth dent, and a Mandelbulb.
+pub fn sdf_scene() -> Scene {
+    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
+    let ground = Material::Lambertian { albedo: Vec3::new(0.5, 0.5, 0.5) };
+    list.push(Box::new(Plane::new(Vec3::new(0.0, 1.0, 0.0), 0.0, ground)));
+
+    let blob = Sdf::Sphere { radius: 0.8 }.translate(Vec3::new(-4.5, 0.8, 0.0))
+        .smooth_union(Sdf::Sphere { radius: 0.6 }.translate(Vec3::new(-3.7, 1.4, 0.3)), 0.5)
+        .smooth_union(Sdf::Sphere { radius: 0.5 }.translate(Vec3::new(-4.6, 1.9, -0.2)), 0.5);
+    list.push(Box::new(SdfObject::new(
+        blob,
+        Aabb::new(Vec3::new(-5.5, 0.0, -1.0), Vec3::new(-3.0, 2.6, 1.0)),
+        Material::Lambertian { albedo: Vec3::new(0.7, 0.2, 0.2) },
+    )));
+
+    let dented = Sdf::RoundBox { half_size: Vec3::new(0.9, 0.9, 0.9), radius: 0.2 }
+        .smooth_subtraction(Sdf::Sphere { radius: 0.8 }.translate(Vec3::new(0.0, 0.9, 0.9)), 0.2)
+        .translate(Vec3::new(0.0, 0.9, 0.0));
+    list.push(Box::new(SdfObject::new(
+        dented,
+        Aabb::new(Vec3::new(-1.0, -0.1, -1.0), Vec3::new(1.0, 1.9, 1.0)),
+        Material::Lambertian { albedo: Vec3::new(0.2, 0.6, 0.2) },
+    )));
+
+    let bulb = Sdf::Mandelbulb { power: 8.0, iterations: 12 }.translate(Vec3::new(4.0, 1.2, 0.0));
+    list.push(Box::new(SdfObject::new(
+        bulb,
+        Aabb::new(Vec3::new(2.7, -0.1, -1.3), Vec3::new(5.3, 2.5, 1.3)),
+        Material::Lambertian { albedo: Vec3::new(0.2, 0.3, 0.7) },
+    )));
+    Scene::new(HittableList::new(list))
+}
+
+/// A bank of forward scattering noise cloud over a ground plane.
+pub fn clouds_scene() -> Scene {
+    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
+    let ground = Material::Lambertian { albedo: Vec3::new(0.4, 0.5, 0.3) };
+    list.push(Box::new(Plane::new(Vec3::new(0.0, 1.0, 0.0), 0.0, ground)));
+
+    list.push(Box::new(HeterogeneousMedium::new(
+        Aabb::new(Vec3::new(-8.0, 2.0, -4.0), Vec3::new(8.0, 6.0, 4.0)),
+        Density::Noise { frequency: 4.0, octaves: 5 },
+        3.0,
+        Vec3::new(0.95, 0.95, 0.95),
+        0.6,
+    )));
+    Scene::new(HittableList::new(list))
+}
+
+/// A row of microfacet balls: gold, copper and aluminium of increasing
+/// roughness, then smooth and frosted glass. Behind them principled
+/// varnished plastic, brushed metal, tinted glass, velvet and a glowing ball,
+/// on a varnished checkerboard.
+pub fn materials_scene() -> Scene {
+    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
+    // Varnished checkerboard floor
+    let tiles = Material::mix(
+        Material::Lambertian { albedo: Vec3::new(0.8, 0.8, 0.75) },
+        Material::Lambertian { albedo: Vec3::new(0.1, 0.1, 0.12) },
+        Texture::Checker { even: Vec3::default(), odd: Vec3::new(1.0, 1.0, 1.0), frequency: 0.5 },
+    );
+    let ground = Material::coated(tiles, 1.5, 0.0);
+    list.push(Box::new(Plane::new(Vec3::new(0.0, 1.0, 0.0), 0.0, ground)));
+
+    let materials = [
+        Material::gold(0.05),
+        Material::copper(0.25),
+        Material::aluminium(0.5),
+        Material::Dielectric { ior: 1.5, roughness: 0.0 },
+        Material::Dielectric { ior: 1.5, roughness: 0.3 },
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        let x = (i as Float - 2.0) * 2.2;
+        list.push(Box::new(Sphere::new(Vec3::new(x, 1.0, 0.0), 1.0, material)));
+    }
+
+    let principled = [
+        Principled { base_color: Vec3::new(0.8, 0.1, 0.1), roughness: 0.6, clearcoat: 1.0, ..Default::default() },
+        Principled { base_color: Vec3::new(0.9, 0.9, 0.9), metallic: 1.0, roughness: 0.35, ..Default::default() },
+        Principled { base_color: Vec3::new(0.6, 0.9, 0.7), roughness: 0.05, transmission: 1.0, ..Default::default() },
+        Principled { base_color: Vec3::new(0.2, 0.1, 0.5), roughness: 1.0, sheen: 1.0, ..Default::default() },
+        Principled { base_color: Vec3::new(0.1, 0.1, 0.1), emission: Vec3::new(4.0, 3.0, 1.5), ..Default::default() },
+    ];
+    for (i, principled) in principled.into_iter().enumerate() {
+        let x = (i as Float - 2.0) * 2.2;
+        list.push(Box::new(Sphere::new(Vec3::new(x, 1.0, -3.0), 1.0, Material::Principled(principled))));
+    }
+    Scene::new(HittableList::new(list))
+}
+
+/// Tangent space normal map of a grid of domes, `tiles` across, each texel
+/// the dome normal remapped to `[0, 1]`.
+fn dome_normal_map(size: usize, tiles: usize) -> Image {
+    let mut texels = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            // Position within the tile in [-1, 1], y up like v
+            let tile = |i: usize| ((i * tiles) as Float / size as Float).fract() * 2.0 - 1.0;
+            let (dx, dy) = (tile(x), tile(size - 1 - y));
+            let r2 = dx * dx + dy * dy;
+            let n = if r2 < 0.64 { Vec3::new(dx, dy, (1.0 - r2).sqrt()) } else { Vec3::new(0.0, 0.0, 1.0) };
+            texels.push((Vec3::unit_vector(&n) + Vec3::new(1.0, 1.0, 1.0)) * 0.5);
+        }
+    }
+    Image::new(size, size, texels)
+}
+
+/// Surface detail without geometry: a normal mapped ball, a bump mapped
+/// copper ball and a normal mapped panel made of two triangles.
+pub fn detail_scene() -> Scene {
+    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
+    let ground = Material::Lambertian { albedo: Vec3::new(0.5, 0.5, 0.5) };
+    list.push(Box::new(Plane::new(Vec3::new(0.0, 1.0, 0.0), 0.0, ground)));
+
+    let domes = Arc::new(dome_normal_map(256, 8));
+    let ball = Sphere::new(Vec3::new(-3.0, 1.2, 0.0), 1.2, Material::Lambertian { albedo: Vec3::new(0.8, 0.4, 0.1) });
+    list.push(Box::new(NormalMapped::new(Box::new(ball), NormalMap::Tangent(Texture::Image(domes.clone())))));
+
+    let ball = Sphere::new(Vec3::new(0.0, 1.2, 0.0), 1.2, Material::copper(0.3));
+    let noise = Texture::Noise { low: Vec3::default(), high: Vec3::new(1.0, 1.0, 1.0), frequency: 3.0, octaves: 4 };
+    list.push(Box::new(NormalMapped::new(Box::new(ball), NormalMap::Bump { height: noise, scale: 0.05 })));
+
+    let panel = Material::Principled(Principled { base_color: Vec3::new(0.2, 0.4, 0.8), roughness: 0.3, ..Default::default() });
+    let corners = [Vec3::new(1.8, 0.0, 0.0), Vec3::new(4.2, 0.0, 0.0), Vec3::new(4.2, 2.4, 0.0), Vec3::new(1.8, 2.4, 0.0)];
+    let triangles = [
+        Triangle::with_uvs([corners[0], corners[1], corners[2]], [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)], panel.clone()),
+        Triangle::with_uvs([corners[0], corners[2], corners[3]], [(0.0, 0.0), (1.0, 1.0), (0.0, 1.0)], panel),
+    ];
+    for triangle in triangles {
+        list.push(Box::new(NormalMapped::new(Box::new(triangle), NormalMap::Tangent(Texture::Image(domes.clone())))));
+    }
+    Scene::new(HittableList::new(list))
+}
+
+pub fn cornell_box() -> Scene {
+    let mut list: Vec<Box<dyn Hittable>> = Vec::new();
+
+    // Set up the objects in the scene
+    let red = Material::Lambertian { albedo: Vec3::new(0.65, 0.05, 0.05) };
+    let white = Material::Lambertian { albedo: Vec3::new(0.73, 0.73, 0.73) };
+
+    // Add the floor
+    list.push(Box::new(Plane::new(Vec3::new(0.0, 1.0, 0.0), 0.0, white.clone())));
+
+    // Add the roof
+    list.push(Box::new(Plane::new(Vec3::new(0.0, 1.0, 0.0), 40.0, white.clone())));
+
+    // Add the backwall
+    list.push(Box::new(Plane::new(Vec3::new(1.0, 0.0, 0.0), 0.0, white.clone())));
+
+    // Add the leftwall
+    list.push(Box::new(Plane::new(Vec3::new(0.0, 0.0, 1.0), 20.0, white.clone())));
+
+    // Add the rightwall
+    list.push(Box::new(Plane::new(Vec3::new(0.0, 0.0, 1.0), -20.0, white.clone())));
+
+    // Column of smoke at the back of the box
+    let smoke = Cube::new(Vec3::new(4.0, 0.0, -15.0), Vec3::new(14.0, 30.0, -5.0), white);
+    list.push(Box::new(ConstantMedium::new(Box::new(smoke), 0.1, Vec3::new(0.2, 0.2, 0.2))));
+
+    //center sphere
+    list.push(Box::new(Sphere::new(
+        Vec3::new(10.0, 10.0, 0.0),
+        5.0,
+        red
+    )));
+
+    Scene::new(HittableList::new(list))
+}
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use crate::float::Float;
+use crate::vec3::Vec3;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::aabb::Aabb;
+
+/// Marching stops after this many steps without reaching the surface.
+const MAX_STEPS: u32 = 256;
+
+/// Distance below which the march counts as a hit. Also used as the step
+/// for the gradient that gives the normal.
+const HIT_EPSILON: Float = 1e-4;
+
+/// Signed distance function: negative inside the shape, positive outside
+/// and never more than the distance to the surface. Shapes are centred on
+/// the origin; use `translate` to move them.
+#[derive(Clone)]
+pub enum Sdf {
+    Sphere { radius: Float },
+    Box { half_size: Vec3 },
+    /// Box with its edges rounded off by `radius`.
+    RoundBox { half_size: Vec3, radius: Float },
+    /// Ring around the y axis.
+    Torus { major_radius: Float, minor_radius: Float },
+    /// Distance estimate of the power `power` Mandelbulb fractal.
+    Mandelbulb { power: Float, iterations: u32 },
+    Translate { offset: Vec3, sdf: Box<Sdf> },
+    Union(Box<Sdf>, Box<Sdf>),
+    /// Union blending the two shapes over a distance of about `k`.
+    SmoothUnion { a: Box<Sdf>, b: Box<Sdf>, k: Float },
+    /// `a` with `b` carved out of it, blended over a distance of about `k`.
+    SmoothSubtraction { a: Box<Sdf>, b: Box<Sdf>, k: Float },
+    /// Any user supplied distance function.
+    Custom(Arc<dyn Fn(Vec3) -> Float + Send + Sync>),
+}
+
+impl Sdf {
+    pub fn translate(self, offset: Vec3) -> Sdf {
+        Sdf::Translate { offset, sdf: Box::new(self) }
+    }
+
+    pub fn union(self, other: Sdf) -> Sdf {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Sdf, k: Float) -> Sdf {
+        Sdf::SmoothUnion { a: Box::new(self), b: Box::new(other), k }
+    }
+
+    pub fn smooth_subtraction(self, other: Sdf, k: Float) -> Sdf {
+        Sdf::SmoothSubtraction { a: Box::new(self), b: Box::new(other), k }
+    }
+
+    pub fn distance(&self, p: Vec3) -> Float {
+        match self {
+            Sdf::Sphere { radius } => p.length() - radius,
+            Sdf::Box { half_size } => box_distance(p, *half_size),
+            Sdf::RoundBox { half_size, radius } => {
+                box_distance(p, *half_size - Vec3::new(*radius, *radius, *radius)) - radius
+            }
+            Sdf::Torus { major_radius, minor_radius } => {
+                let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - major_radius;
+                (ring * ring + p.y() * p.y()).sqrt() - minor_radius
+            }
+            Sdf::Mandelbulb { power, iterations } => mandelbulb_distance(p, *power, *iterations),
+            Sdf::Translate { offset, sdf } => sdf.distance(p - *offset),
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::SmoothUnion { a, b, k } => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+                b * (1.0 - h) + a * h - k * h * (1.0 - h)
+            }
+            Sdf::SmoothSubtraction { a, b, k } => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 - 0.5 * (a + b) / k).clamp(0.0, 1.0);
+                a * (1.0 - h) - b * h + k * h * (1.0 - h)
+            }
+            Sdf::Custom(f) => f(p),
+        }
+    }
+
+    /// Unit normal from the central difference gradient of the distance.
+    pub fn normal(&self, p: Vec3) -> Vec3 {
+        let h = HIT_EPSILON;
+        let gradient = Vec3::new(
+            self.distance(p + Vec3::new(h, 0.0, 0.0)) - self.distance(p - Vec3::new(h, 0.0, 0.0)),
+            self.distance(p + Vec3::new(0.0, h, 0.0)) - self.distance(p - Vec3::new(0.0, h, 0.0)),
+            self.distance(p + Vec3::new(0.0, 0.0, h)) - self.distance(p - Vec3::new(0.0, 0.0, h)),
+        );
+        Vec3::unit_vector(&gradient)
+    }
+}
+
+fn box_distance(p: Vec3, half_size: Vec3) -> Float {
+    let q = p.abs() - half_size;
+    let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).length();
+    outside + q.x().max(q.y()).max(q.z()).min(0.0)
+}
+
+/// Hubbard-Douady style distance estimate, `0.5 r ln(r) / |dz|`.
+fn mandelbulb_distance(p: Vec3, power: Float, iterations: u32) -> Float {
+    let mut z = p;
+    let mut dr = 1.0;
+    let mut r = z.length();
+    for _ in 0..iterations {
+        if r > 2.0 {
+            break;
+        }
+        // Back at the origin the orbit repeats from p, so p is inside
+        if r == 0.0 {
+            return 0.0;
+        }
+        let theta = (z.z() / r).acos() * power;
+        let phi = z.y().atan2(z.x()) * power;
+        dr = r.powf(power - 1.0) * power * dr + 1.0;
+        let zr = r.powf(power);
+        z = Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) * zr + p;
+        r = z.length();
+    }
+    if r == 0.0 {
+        return 0.0;
+    }
+    0.5 * r.ln() * r / dr
+}
+
+/// Renders an `Sdf` by sphere tracing: step along the ray by the distance
+/// to the nearest surface until it is almost zero. `bounds` must contain
+/// the whole shape; marching only happens inside it.
+pub struct SdfObject {
+    sdf: Sdf,
+    bounds: Aabb,
+    material: Material,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Sdf, bounds: Aabb, material: Material) -> SdfObject {
+        SdfObject {
+            sdf,
+            bounds,
+            material,
+        }
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (t_start, t_end) = self.bounds.clip(r, t_min, t_max)?;
+        let speed = r.direction().length();
+        // Starting inside, the distance is negative until the ray gets out
+        let side = self.sdf.distance(r.point_at_parameter(t_start)).signum();
+
+        let mut t = t_start;
+        for _ in 0..MAX_STEPS {
+            let p = r.point_at_parameter(t);
+            let distance = side * self.sdf.distance(p);
+            if distance < HIT_EPSILON {
+                let mut rec = HitRecord {
+                    t,
+                    p,
+                    // The surface is only known to within the hit epsilon, so
+                    // spawned rays have to start well clear of that, with some
+                    // slack for distance estimates that are not exact
+                    p_error: Vec3::new(4.0, 4.0, 4.0) * HIT_EPSILON,
+                    material: self.material.clone(),
+                    ..Default::default()
+                };
+                rec.set_face_normal(r, self.sdf.normal(p));
+                return Some(rec);
+            }
+            t += distance / speed;
+            if t >= t_end {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mandelbulb_distance_is_finite_at_the_origin() {
+        let bulb = Sdf::Mandelbulb { power: 8.0, iterations: 10 };
+        assert_eq!(bulb.distance(Vec3::default()), 0.0);
+        assert!(bulb.distance(Vec3::new(0.0, 0.0, 1e-3)).is_finite());
+        assert!(bulb.distance(Vec3::new(3.0, 0.0, 0.0)) > 0.0);
+    }
+}
@@ -0,0 +1,241 @@
+//! Daylight from the Preetham, Shirley and Smits analytic sky model, with the
+//! sun as a small bright disk.
+
+use crate::float::Float;
+use crate::float::consts::PI;
+use crate::matrix::Mat3;
+use crate::sampler::{sample_cone, sample_unit_sphere};
+use crate::vec3::Vec3;
+
+/// Angular radius of the sun seen from the ground, in radians.
+const SUN_ANGULAR_RADIUS: Float = 0.00465;
+/// Luminance of the sun before the atmosphere dims it, in kcd/m^2.
+const SUN_LUMINANCE: Float = 2.0e6;
+/// The model works in kcd/m^2. This brings it down so full sun on a white
+/// surface is about 3 and the sky is a little darker than the old gradient.
+const LUMINANCE_SCALE: Float = 0.025;
+/// How often `sample` aims at the sun while it is up.
+const SUN_SAMPLE_PROBABILITY: Float = 0.5;
+
+/// Clear sky lit by the sun from `sun`. Turbidity runs from about 2 for very
+/// clear air to 10 for haze.
+#[derive(Debug)]
+pub struct Sky {
+    sun: Vec3,
+    /// Zenith luminance Y and chromaticity x, y.
+    zenith: [Float; 3],
+    /// Perez distribution coefficients A to E, for Y, x and y.
+    perez: [[Float; 5]; 3],
+    sun_radiance: Vec3,
+    cos_sun_radius: Float,
+}
+
+impl Sky {
+    pub fn new(sun: Vec3, turbidity: Float) -> Sky {
+        let sun = Vec3::unit_vector(&sun);
+        let t = turbidity;
+        let theta_s = sun.y().clamp(-1.0, 1.0).acos();
+        let (theta2, theta3) = (theta_s * theta_s, theta_s * theta_s * theta_s);
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let zenith_x = t * t * (0.00166 * theta3 - 0.00375 * theta2 + 0.00209 * theta_s)
+            + t * (-0.02903 * theta3 + 0.06377 * theta2 - 0.03202 * theta_s + 0.00394)
+            + (0.11693 * theta3 - 0.21196 * theta2 + 0.06052 * theta_s + 0.25886);
+        let zenith_chroma_y = t * t * (0.00275 * theta3 - 0.00610 * theta2 + 0.00317 * theta_s)
+            + t * (-0.04214 * theta3 + 0.08970 * theta2 - 0.04153 * theta_s + 0.00516)
+            + (0.15346 * theta3 - 0.26756 * theta2 + 0.06670 * theta_s + 0.26688);
+
+        let perez = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+
+        Sky {
+            sun,
+            zenith: [zenith_y, zenith_x, zenith_chroma_y],
+            perez,
+            sun_radiance: Sky::sun_radiance(theta_s, turbidity),
+            cos_sun_radius: SUN_ANGULAR_RADIUS.cos(),
+        }
+    }
+
+    /// Direction of the sun `elevation` degrees above the horizon and
+    /// `azimuth` degrees around from -z towards +x.
+    pub fn sun_direction(elevation: Float, azimuth: Float) -> Vec3 {
+        let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+        Vec3::new(elevation.cos() * azimuth.sin(), elevation.sin(), -elevation.cos() * azimuth.cos())
+    }
+
+    /// Sunlight left after Rayleigh and aerosol scattering on its way through
+    /// the air, at red, green and blue wavelengths, for a sun `theta_s` from
+    /// the zenith.
+    fn sun_radiance(theta_s: Float, turbidity: Float) -> Vec3 {
+        if theta_s >= PI / 2.0 {
+            return Vec3::default();
+        }
+        // Relative optical mass of the air the light passes through
+        let m = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * turbidity - 0.04586;
+        let transmittance = |lambda: Float| {
+            let rayleigh = (-0.008735 * lambda.powf(-4.08) * m).exp();
+            let aerosol = (-beta * lambda.powf(-1.3) * m).exp();
+            rayleigh * aerosol
+        };
+        Vec3::new(transmittance(0.680), transmittance(0.550), transmittance(0.440)) * (SUN_LUMINANCE * LUMINANCE_SCALE)
+    }
+
+    /// Perez et al.'s relative distribution over the sky.
+    fn perez(coefficients: &[Float; 5], cos_theta: Float, gamma: Float) -> Float {
+        let [a, b, c, d, e] = *coefficients;
+        (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos() * gamma.cos())
+    }
+
+    /// Radiance of the sky alone, without the sun disk.
+    fn sky_radiance(&self, direction: Vec3) -> Vec3 {
+        // Below the horizon the model has nothing to say, so the horizon
+        // carries on down
+        let cos_theta = direction.y().max(0.001);
+        let cos_gamma = Vec3::dot(&direction, &self.sun).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+        let theta_s = self.sun.y().clamp(-1.0, 1.0).acos();
+
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * Sky::perez(&self.perez[i], cos_theta, gamma) / Sky::perez(&self.perez[i], 1.0, theta_s)
+        });
+        if y <= 0.0 {
+            return Vec3::default();
+        }
+
+        // xyY to XYZ to linear sRGB
+        let luminance = luminance * LUMINANCE_SCALE;
+        let (cx, cy, cz) = (x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+        Vec3::new(
+            (3.2406 * cx - 1.5372 * cy - 0.4986 * cz).max(0.0),
+            (-0.9689 * cx + 1.8758 * cy + 0.0415 * cz).max(0.0),
+            (0.0557 * cx - 0.2040 * cy + 1.0570 * cz).max(0.0),
+        )
+    }
+
+    fn in_sun(&self, direction: Vec3) -> bool {
+        Vec3::dot(&direction, &self.sun) >= self.cos_sun_radius
+    }
+
+    fn sun_probability(&self) -> Float {
+        if self.sun_radiance.squared_length() > 0.0 { SUN_SAMPLE_PROBABILITY } else { 0.0 }
+    }
+
+    fn sun_pdf(&self) -> Float {
+        1.0 / (2.0 * PI * (1.0 - self.cos_sun_radius))
+    }
+
+    /// Radiance arriving from `direction`, which need not be normalized.
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        let direction = Vec3::unit_vector(&direction);
+        let sky = self.sky_radiance(direction);
+        if self.in_sun(direction) { sky + self.sun_radiance } else { sky }
+    }
+
+    /// Aims at the sun disk part of the time and anywhere on the sphere
+    /// otherwise, returning the direction, its radiance and density.
+    pub fn sample(&self, u: (Float, Float)) -> (Vec3, Vec3, Float) {
+        let sun_probability = self.sun_probability();
+        let direction = if u.0 < sun_probability {
+            let u = (u.0 / sun_probability, u.1);
+            Mat3::onb(self.sun) * sample_cone(u, self.cos_sun_radius)
+        } else {
+            let u = ((u.0 - sun_probability) / (1.0 - sun_probability), u.1);
+            sample_unit_sphere(u)
+        };
+        (direction, self.radiance(direction), self.pdf(direction))
+    }
+
+    /// Density of `sample` returning `direction`.
+    pub fn pdf(&self, direction: Vec3) -> Float {
+        let sun_probability = self.sun_probability();
+        let sphere = (1.0 - sun_probability) / (4.0 * PI);
+        if self.in_sun(Vec3::unit_vector(&direction)) { sphere + sun_probability * self.sun_pdf() } else { sphere }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-9;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-5;
+
+    /// The sun disk edge is blurred by rounding in single precision.
+    #[cfg(not(feature = "f32"))]
+    const INTEGRAL_TOLERANCE: Float = 1e-3;
+    #[cfg(feature = "f32")]
+    const INTEGRAL_TOLERANCE: Float = 1e-2;
+
+    #[test]
+    fn sun_direction_at_known_angles() {
+        let diagonal = Float::sqrt(6.0) / 4.0;
+        let cases = [
+            ((90.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+            ((0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+            ((0.0, 90.0), Vec3::new(1.0, 0.0, 0.0)),
+            ((0.0, 180.0), Vec3::new(0.0, 0.0, 1.0)),
+            ((30.0, 45.0), Vec3::new(diagonal, 0.5, -diagonal)),
+        ];
+        for ((elevation, azimuth), expected) in cases {
+            let sun = Sky::sun_direction(elevation, azimuth);
+            assert!((sun - expected).length() < TOLERANCE, "{} {}: {:?} != {:?}", elevation, azimuth, sun, expected);
+        }
+    }
+
+    #[test]
+    fn sun_samples_land_in_the_disk_with_their_pdf() {
+        const SAMPLES: usize = 100000;
+        let sky = Sky::new(Sky::sun_direction(40.0, 120.0), 3.0);
+        let mut rng = StdRng::seed_from_u64(49);
+        let mut in_disk = 0;
+        for _ in 0..SAMPLES {
+            let u = (rng.gen::<Float>(), rng.gen::<Float>());
+            let (direction, radiance, pdf) = sky.sample(u);
+            assert!((direction.length() - 1.0).abs() < 1e2 * TOLERANCE);
+            assert_eq!(pdf, sky.pdf(direction));
+            assert_eq!(radiance, sky.radiance(direction));
+            if u.0 < SUN_SAMPLE_PROBABILITY {
+                assert!(sky.in_sun(direction));
+            }
+            in_disk += usize::from(sky.in_sun(direction));
+        }
+        // The sphere samples hardly ever hit a disk this small
+        let fraction = in_disk as Float / SAMPLES as Float;
+        assert!((fraction - SUN_SAMPLE_PROBABILITY).abs() < 4.0 * (0.25 / SAMPLES as Float).sqrt());
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        // In polar coordinates around the sun, with a separate fine grid over
+        // the disk since it is far smaller than any step over the sphere
+        const STEPS: usize = 2000;
+        for sun in [Sky::sun_direction(40.0, 120.0), Sky::sun_direction(-10.0, 0.0)] {
+            let sky = Sky::new(sun, 3.0);
+            let frame = Mat3::onb(sky.sun);
+            let edge = 2.0 * SUN_ANGULAR_RADIUS;
+            let band = |from: Float, to: Float| {
+                let dtheta = (to - from) / STEPS as Float;
+                (0..STEPS)
+                    .map(|i| {
+                        let theta = from + (i as Float + 0.5) * dtheta;
+                        // The density is the same all around the sun
+                        let direction = frame * Vec3::new(theta.sin(), 0.0, theta.cos());
+                        sky.pdf(direction) * theta.sin() * dtheta * 2.0 * PI
+                    })
+                    .sum::<Float>()
+            };
+            let total = band(0.0, edge) + band(edge, PI);
+            assert!((total - 1.0).abs() < INTEGRAL_TOLERANCE, "{:?}: {}", sun, total);
+        }
+    }
+}
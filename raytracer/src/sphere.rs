@@ -1,54 +1,179 @@
+use crate::float::{Float, gamma};
+use crate::float::consts::PI;
 use crate::vec3::Vec3;
 use crate::ray::Ray;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::aabb::Aabb;
+#[cfg(feature = "simd")]
+use crate::packet::{self, RayPacket4};
 
 pub struct Sphere {
     center: Vec3,
-    radius: f64,
+    radius: Float,
     material: Material,
 }
 
 impl Sphere {
-    pub fn sphere(center: Vec3, radius: f64, material: Material) -> Sphere {
+    pub fn new(center: Vec3, radius: Float, material: Material) -> Sphere {
         Sphere {
             center,
             radius,
             material,
         }
     }
-    
-}
 
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    /// Both ray parameters where `r` crosses the sphere, nearest first, or
+    /// `None` if it misses or only grazes it.
+    fn roots(&self, r: &Ray) -> Option<(Float, Float)> {
         let oc = r.origin() - self.center;
         let a = Vec3::dot(&r.direction(), &r.direction());
         let b = Vec3::dot(&oc, &r.direction());
         let c = oc.squared_length() - self.radius*self.radius;
         let discriminant = b*b - a*c;
+        if discriminant <= 0.0 {
+            return None;
+        }
+        Some(((-b - discriminant.sqrt()) / a, (-b + discriminant.sqrt()) / a))
+    }
+
+    /// Hit record at parameter `t` along `r`. The hit point is projected back
+    /// onto the sphere, which bounds its error independently of `t`.
+    fn record(&self, r: &Ray, t: Float) -> HitRecord {
+        let mut offset = r.point_at_parameter(t) - self.center;
+        offset = offset * (self.radius.abs() / offset.length());
+        let p = self.center + offset;
+
+        // Latitude v from the bottom pole up, longitude u around the y axis
+        let (x, y, z) = (offset.x(), offset.y(), offset.z());
+        let theta = (-y / self.radius.abs()).clamp(-1.0, 1.0).acos();
+        let phi = (-z).atan2(x) + PI;
+        let rho = (x * x + z * z).sqrt();
+        let dpdv = if rho > 0.0 {
+            Vec3::new(-x * y / rho, rho, -z * y / rho) * PI
+        } else {
+            Vec3::default()
+        };
+
+        let mut rec = HitRecord {
+            t,
+            p,
+            p_error: offset.abs() * gamma(5) + p.abs() * gamma(1),
+            u: phi / (2.0 * PI),
+            v: theta / PI,
+            dpdu: Vec3::new(z, 0.0, -x) * (2.0 * PI),
+            dpdv,
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(r, offset / self.radius);
+        rec
+    }
+}
 
-        if discriminant > 0.0 {
-            let mut temp = (-b - discriminant.sqrt()) / a;
-            if temp < t_max && temp > t_min {
-                return Some(HitRecord {
-                    t: temp,
-                    p: r.point_at_parameter(temp),
-                    normal: (r.point_at_parameter(temp) - self.center) / self.radius,
-                    material: self.material,
-                });
+#[cfg(feature = "simd")]
+impl Sphere {
+    /// Intersects four rays at once, with the same result per lane as `hit`.
+    pub fn hit_packet(&self, packet: &RayPacket4, t_min: Float, t_max: Float) -> [Option<HitRecord>; 4] {
+        use wide::{CmpGt, CmpLt};
+        use crate::float::FloatX4;
+
+        let center = [FloatX4::splat(self.center.x()), FloatX4::splat(self.center.y()), FloatX4::splat(self.center.z())];
+        let oc = [packet.origin[0] - center[0], packet.origin[1] - center[1], packet.origin[2] - center[2]];
+        let a = packet::dot(&packet.direction, &packet.direction);
+        let b = packet::dot(&oc, &packet.direction);
+        let c = packet::dot(&oc, &oc) - FloatX4::splat(self.radius * self.radius);
+        let discriminant = b * b - a * c;
+        let root = discriminant.max(FloatX4::splat(0.0)).sqrt();
+
+        let (t_min, t_max) = (FloatX4::splat(t_min), FloatX4::splat(t_max));
+        let near = (-b - root) / a;
+        let far = (-b + root) / a;
+        let near_ok = near.simd_gt(t_min) & near.simd_lt(t_max);
+        let far_ok = far.simd_gt(t_min) & far.simd_lt(t_max);
+        let hit = discriminant.simd_gt(FloatX4::splat(0.0)) & (near_ok | far_ok);
+        let t = near_ok.blend(near, far).to_array();
+        let mask = hit.to_bitmask();
+
+        std::array::from_fn(|lane| {
+            if mask & (1 << lane) == 0 {
+                return None;
             }
-            temp = (-b + discriminant.sqrt()) / a;
-            if temp < t_max && temp > t_min {
-                return Some(HitRecord {
-                    t: temp,
-                    p: r.point_at_parameter(temp),
-                    normal: (r.point_at_parameter(temp) - self.center) / self.radius,
-                    material: self.material,
-                });
+            Some(self.record(&packet.ray(lane), t[lane]))
+        })
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (near, far) = self.roots(r)?;
+        if near < t_max && near > t_min {
+            return Some(self.record(r, near));
+        }
+        if far < t_max && far > t_min {
+            return Some(self.record(r, far));
+        }
+        None
+    }
+
+    fn hit_all(&self, r: &Ray) -> Vec<HitRecord> {
+        match self.roots(r) {
+            Some((near, far)) => vec![self.record(r, near), self.record(r, far)],
+            None => Vec::new(),
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius.abs(), self.radius.abs(), self.radius.abs());
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
+
+#[cfg(all(test, feature = "simd"))]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const CASES: usize = 1000;
+
+    fn random_vec3(rng: &mut StdRng, range: Float) -> Vec3 {
+        Vec3::new(rng.gen_range(-range..range), rng.gen_range(-range..range), rng.gen_range(-range..range))
+    }
+
+    /// Four rays from around the sphere aimed near it, so some lanes miss,
+    /// some start inside and some only hit beyond `t_max`.
+    fn random_rays(rng: &mut StdRng, center: Vec3) -> [Ray; 4] {
+        std::array::from_fn(|_| {
+            let origin = center + random_vec3(rng, 4.0);
+            let target = center + random_vec3(rng, 2.0);
+            Ray::new(origin, (target - origin) * rng.gen_range(0.5..2.0))
+        })
+    }
+
+    #[test]
+    fn packet_lanes_match_scalar_hits() {
+        let mut rng = StdRng::seed_from_u64(33);
+        let sphere = Sphere::new(Vec3::new(0.5, -0.2, 0.3), 1.2, Material::default());
+        let (mut hits, mut misses) = (0, 0);
+        for _ in 0..CASES {
+            let rays = random_rays(&mut rng, sphere.center);
+            let (t_min, t_max) = (rng.gen_range(0.0..0.5), rng.gen_range(0.5..3.0));
+            let packet = RayPacket4::new(&rays);
+            let lanes = sphere.hit_packet(&packet, t_min, t_max);
+            for (ray, lane) in rays.iter().zip(lanes) {
+                match (sphere.hit(ray, t_min, t_max), lane) {
+                    (None, None) => misses += 1,
+                    (Some(scalar), Some(packed)) => {
+                        assert!((scalar.t - packed.t).abs() <= 1e-5 * scalar.t.abs().max(1.0));
+                        assert_eq!(scalar.front_face, packed.front_face);
+                        assert!((Vec3::from(scalar.normal) - Vec3::from(packed.normal)).length() < 1e-4);
+                        hits += 1;
+                    }
+                    (scalar, packed) => panic!("{:?}: scalar {:?}, packet {:?}", ray, scalar.map(|r| r.t), packed.map(|r| r.t)),
+                }
             }
-            
         }
-        return None;
+        assert!(hits > 100 && misses > 100, "{} hits, {} misses", hits, misses);
     }
-}
\ No newline at end of file
+}
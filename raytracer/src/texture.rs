@@ -0,0 +1,118 @@
+//! Spatially varying colours looked up at a hit.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use image::codecs::hdr::HdrDecoder;
+
+use crate::float::Float;
+use crate::noise;
+use crate::vec3::Vec3;
+
+/// Grid of texels covering `[0, 1]^2` in UV space, v pointing up the image.
+#[derive(Debug)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    texels: Vec<Vec3>,
+}
+
+impl Image {
+    /// `width * height` texels row by row from the top left.
+    pub fn new(width: usize, height: usize, texels: Vec<Vec3>) -> Image {
+        assert_eq!(texels.len(), width * height, "texel count does not match the size");
+        Image { width, height, texels }
+    }
+
+    /// Loads an image file with its values as stored, without undoing any
+    /// gamma, which is what normal and height maps need. Radiance `.hdr`
+    /// files keep their full range.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Image, image::ImageError> {
+        let path = path.as_ref();
+        if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("hdr")) {
+            return Image::open_hdr(path);
+        }
+        let rgb = image::open(path)?.to_rgb32f();
+        let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+        let texels = rgb.pixels().map(|p| Vec3::new(p[0] as Float, p[1] as Float, p[2] as Float)).collect();
+        Ok(Image::new(width, height, texels))
+    }
+
+    /// `image::open` squeezes Radiance files into eight bits, so they are
+    /// decoded here instead.
+    fn open_hdr(path: &Path) -> Result<Image, image::ImageError> {
+        let decoder = HdrDecoder::new(BufReader::new(File::open(path).map_err(image::ImageError::IoError)?))?;
+        let metadata = decoder.metadata();
+        let texels = decoder.read_image_hdr()?.iter().map(|p| Vec3::new(p[0] as Float, p[1] as Float, p[2] as Float)).collect();
+        Ok(Image::new(metadata.width as usize, metadata.height as usize, texels))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Texel in column `x` and row `y`, wrapping around at the edges.
+    pub fn texel(&self, x: i64, y: i64) -> Vec3 {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        self.texels[y * self.width + x]
+    }
+
+    /// Bilinearly filtered value at `(u, v)`, repeating outside `[0, 1]`.
+    pub fn lookup(&self, u: Float, v: Float) -> Vec3 {
+        let x = u * self.width as Float - 0.5;
+        let y = (1.0 - v) * self.height as Float - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Texture {
+    Constant(Vec3),
+    /// 3D checkerboard of cubes `1 / frequency` on a side.
+    Checker { even: Vec3, odd: Vec3, frequency: Float },
+    /// Turbulent fractal noise between `low` and `high`.
+    Noise { low: Vec3, high: Vec3, frequency: Float, octaves: u32 },
+    /// Image wrapped over the UVs.
+    Image(Arc<Image>),
+}
+
+impl Texture {
+    pub fn constant(value: Float) -> Texture {
+        Texture::Constant(Vec3::new(value, value, value))
+    }
+
+    /// Value at surface coordinates `(u, v)` and position `p`.
+    pub fn value(&self, u: Float, v: Float, p: Vec3) -> Vec3 {
+        match *self {
+            Texture::Constant(value) => value,
+            Texture::Checker { even, odd, frequency } => {
+                let q = p * frequency;
+                let sum = q.x().floor() + q.y().floor() + q.z().floor();
+                if sum.rem_euclid(2.0) == 0.0 { even } else { odd }
+            }
+            Texture::Noise { low, high, frequency, octaves } => {
+                let t = noise::fbm(p * frequency, octaves).abs().min(1.0);
+                low * (1.0 - t) + high * t
+            }
+            Texture::Image(ref image) => image.lookup(u, v),
+        }
+    }
+
+    /// Grey value, the mean of the channels, for textures used as masks.
+    pub fn scalar(&self, u: Float, v: Float, p: Vec3) -> Float {
+        let value = self.value(u, v, p);
+        (value.x() + value.y() + value.z()) / 3.0
+    }
+}
@@ -0,0 +1,185 @@
+use crate::float::{Float, gamma};
+use crate::float::consts::PI;
+use crate::vec3::Vec3;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::roots::solve_quartic;
+
+/// Ring around the y axis through `center`: a tube of `minor_radius` swept
+/// around a circle of `major_radius`. Wrap it in a `Transform` to tilt it.
+pub struct Torus {
+    center: Vec3,
+    major_radius: Float,
+    minor_radius: Float,
+    material: Material,
+}
+
+impl Torus {
+    pub fn new(center: Vec3, major_radius: Float, minor_radius: Float, material: Material) -> Torus {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    /// Ray parameters of all surface hits in increasing order.
+    fn intersections(&self, r: &Ray) -> ([Float; 4], usize) {
+        // The quartic is badly conditioned for far away origins, so solve it
+        // along a unit direction from the point on the ray closest to the centre
+        let length = r.direction().length();
+        let d = r.direction() / length;
+        let shift = -Vec3::dot(&(r.origin() - self.center), &d);
+        let o = r.origin() - self.center + d * shift;
+        let outer = self.major_radius + self.minor_radius;
+        if o.squared_length() > outer * outer {
+            return ([0.0; 4], 0);
+        }
+
+        let r2 = self.major_radius * self.major_radius;
+        let e = o.squared_length() - r2 - self.minor_radius * self.minor_radius;
+        let f = Vec3::dot(&o, &d);
+        let (mut roots, n) = solve_quartic(
+            4.0 * f,
+            2.0 * e + 4.0 * f * f + 4.0 * r2 * d.y() * d.y(),
+            4.0 * f * e + 8.0 * r2 * o.y() * d.y(),
+            e * e - 4.0 * r2 * (self.minor_radius * self.minor_radius - o.y() * o.y()),
+        );
+        for root in roots.iter_mut().take(n) {
+            *root = (*root + shift) / length;
+        }
+        (roots, n)
+    }
+
+    /// Hit record at `t`, with the point projected back onto the tube.
+    fn record(&self, r: &Ray, t: Float) -> HitRecord {
+        let local = r.point_at_parameter(t) - self.center;
+        let radial = Vec3::new(local.x(), 0.0, local.z());
+        let ring = if radial.squared_length() > 0.0 {
+            radial * (self.major_radius / radial.length())
+        } else {
+            Vec3::new(self.major_radius, 0.0, 0.0)
+        };
+        let outward_normal = Vec3::unit_vector(&(local - ring));
+        let local = ring + outward_normal * self.minor_radius;
+        let p = self.center + local;
+
+        // u goes around the y axis, v around the tube
+        let out = ring / self.major_radius;
+        let across = Vec3::dot(&local, &out) - self.major_radius;
+        let v = local.y().atan2(across) / (2.0 * PI) + 0.5;
+        let mut rec = HitRecord {
+            t,
+            p,
+            p_error: local.abs() * gamma(8) + p.abs() * gamma(1),
+            u: local.z().atan2(local.x()) / (2.0 * PI) + 0.5,
+            v,
+            dpdu: Vec3::new(-local.z(), 0.0, local.x()) * (2.0 * PI),
+            dpdv: (Vec3::new(0.0, across, 0.0) - out * local.y()) * (2.0 * PI),
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(r, outward_normal);
+        rec
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (roots, n) = self.intersections(r);
+        roots[..n]
+            .iter()
+            .find(|&&t| t > t_min && t < t_max)
+            .map(|&t| self.record(r, t))
+    }
+
+    fn hit_all(&self, r: &Ray) -> Vec<HitRecord> {
+        let (roots, n) = self.intersections(r);
+        roots[..n].iter().map(|&t| self.record(r, t)).collect()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let outer = (self.major_radius + self.minor_radius).abs();
+        let minor = self.minor_radius.abs();
+        Some(Aabb::new(
+            self.center - Vec3::new(outer, minor, outer),
+            self.center + Vec3::new(outer, minor, outer),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::{check_surface_hit, ray_through};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[cfg(not(feature = "f32"))]
+    const TOLERANCE: Float = 1e-6;
+    #[cfg(feature = "f32")]
+    const TOLERANCE: Float = 1e-3;
+
+    const CASES: usize = 1000;
+
+    fn torus() -> Torus {
+        Torus::new(Vec3::new(0.0, 1.0, 0.0), 2.0, 0.5, Material::default())
+    }
+
+    fn inside(torus: &Torus, p: Vec3) -> bool {
+        let local = p - torus.center;
+        let across = (local.x() * local.x() + local.z() * local.z()).sqrt() - torus.major_radius;
+        across * across + local.y() * local.y() <= torus.minor_radius * torus.minor_radius
+    }
+
+    #[test]
+    fn axis_aligned_rays_cross_the_outer_and_inner_walls() {
+        let torus = torus();
+
+        // Through the centre the ray enters at the outer wall, leaves at the
+        // inner one, crosses the hole and does the same on the other side
+        let across = Ray::new(Vec3::new(5.0, 1.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let hits = torus.hit_all(&across);
+        let expected = [(2.5, true, 1.0), (3.5, false, 1.0), (6.5, true, 1.0), (7.5, false, 1.0)];
+        assert_eq!(hits.len(), expected.len());
+        for (rec, (t, front_face, facing)) in hits.iter().zip(expected) {
+            assert!((rec.t - t).abs() < TOLERANCE, "{} != {}", rec.t, t);
+            assert_eq!(rec.front_face, front_face);
+            assert!((Vec3::from(rec.normal) - Vec3::new(facing, 0.0, 0.0)).length() < TOLERANCE);
+        }
+        // Outer and inner walls sit half a turn apart around the tube
+        assert!(((hits[0].v - hits[1].v).abs() - 0.5).abs() < TOLERANCE);
+
+        let hole = Ray::new(Vec3::new(0.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(torus.hit(&hole, 0.0, Float::MAX).is_none());
+
+        let down = Ray::new(Vec3::new(2.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = torus.hit(&down, 0.0, Float::MAX).unwrap();
+        assert!((rec.t - 8.5).abs() < TOLERANCE);
+        assert!((Vec3::from(rec.normal) - Vec3::new(0.0, 1.0, 0.0)).length() < TOLERANCE);
+        assert!(torus.hit(&down, 8.6, Float::MAX).is_some_and(|rec| (rec.t - 9.5).abs() < TOLERANCE));
+    }
+
+    #[test]
+    fn random_hits_lie_on_the_surface() {
+        let mut rng = StdRng::seed_from_u64(38);
+        let torus = torus();
+        let aabb = torus.bounding_box().unwrap();
+        let mut hits = 0;
+        for _ in 0..CASES {
+            let ray = ray_through(&mut rng, &aabb);
+            let all = torus.hit_all(&ray);
+            assert!(all.windows(2).all(|pair| pair[0].t <= pair[1].t));
+            for rec in &all {
+                check_surface_hit(&torus, &ray, rec, |p| inside(&torus, p));
+            }
+            let nearest = all.iter().find(|rec| rec.t > 0.0).map(|rec| rec.t);
+            assert_eq!(torus.hit(&ray, 0.0, Float::MAX).map(|rec| rec.t), nearest);
+            hits += all.len();
+        }
+        assert!(hits > CASES / 2);
+    }
+}
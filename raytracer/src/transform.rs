@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use crate::float::Float;
+use crate::vec3::{Point3, Vec3};
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::aabb::Aabb;
+use crate::matrix::Mat4;
+
+/// Places any object in the world through an affine transform. Rays are
+/// moved into the object's space, intersected there and the hit moved back,
+/// so the wrapped object never needs to know where it is.
+pub struct Transform {
+    object: Box<dyn Hittable>,
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Transform {
+    /// Wraps `object` in `matrix`. Panics if the matrix cannot be inverted,
+    /// e.g. a scale of zero along some axis.
+    pub fn new(object: Box<dyn Hittable>, matrix: Mat4) -> Transform {
+        let inverse = matrix.inverse().expect("transform matrix must be invertible");
+        Transform {
+            object,
+            matrix,
+            inverse,
+        }
+    }
+
+    /// Places a shared object; many instances can point at the same data.
+    pub fn instance(object: Arc<dyn Hittable>, matrix: Mat4) -> Transform {
+        Transform::new(Box::new(object), matrix)
+    }
+
+    pub fn translate(object: Box<dyn Hittable>, offset: Vec3) -> Transform {
+        Transform::new(object, Mat4::translation(offset))
+    }
+
+    pub fn rotate(object: Box<dyn Hittable>, axis: Vec3, degrees: Float) -> Transform {
+        Transform::new(object, Mat4::rotation(axis, degrees))
+    }
+
+    pub fn scale(object: Box<dyn Hittable>, factor: Vec3) -> Transform {
+        Transform::new(object, Mat4::scaling(factor))
+    }
+
+    /// The ray in the object's space. The direction is not renormalized, so
+    /// t is the same in both spaces.
+    fn to_local(&self, r: &Ray) -> Ray {
+        Ray::new(self.inverse.transform_point(Point3(r.origin())).0, self.inverse.transform_vector(r.direction()))
+    }
+
+    /// Moves a hit found in the object's space back into the world.
+    fn to_world(&self, mut rec: HitRecord) -> HitRecord {
+        (rec.p, rec.p_error) = self.matrix.transform_point_with_error(rec.p, rec.p_error);
+        rec.normal = self.inverse.transform_normal(rec.normal);
+        rec.shading_normal = self.inverse.transform_normal(rec.shading_normal);
+        rec.dpdu = self.matrix.transform_vector(rec.dpdu);
+        rec.dpdv = self.matrix.transform_vector(rec.dpdv);
+        rec
+    }
+
+    /// Applies `matrix` after the current transform.
+    pub fn then(self, matrix: Mat4) -> Transform {
+        Transform::new(self.object, matrix * self.matrix)
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let rec = self.object.hit(&self.to_local(r), t_min, t_max)?;
+        Some(self.to_world(rec))
+    }
+
+    fn hit_all(&self, r: &Ray) -> Vec<HitRecord> {
+        self.object.hit_all(&self.to_local(r)).into_iter().map(|rec| self.to_world(rec)).collect()
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: Float, t_max: Float) -> Float {
+        self.object.transmittance(&self.to_local(r), t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let local = self.object.bounding_box()?;
+        let corners = local.corners().map(|c| self.matrix.transform_point(Point3(c)).0);
+        let first = Aabb::new(corners[0], corners[0]);
+        Some(corners.iter().fold(first, |acc, c| Aabb::surrounding(&acc, &Aabb::new(*c, *c))))
+    }
+}
@@ -1,64 +1,106 @@
+use crate::float::{Float, gamma};
 use crate::vec3::Vec3;
 use crate::ray::Ray;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::matrix::Mat3;
+use crate::plane::FLAT_BOX_DELTA;
 
+/// Triangle facing the side from which its vertices run counter-clockwise,
+/// with texture coordinates at each vertex.
 pub struct Triangle {
     vertices: [Vec3; 3],
+    uvs: [(Float, Float); 3],
     material: Material,
 }
 
 impl Triangle {
-    pub fn triangle(vertices: [Vec3; 3], material: Material) -> Sphere {
+    /// Triangle with the UVs of half the unit square, (0, 0), (1, 0) and (0, 1).
+    pub fn new(vertices: [Vec3; 3], material: Material) -> Triangle {
+        Triangle::with_uvs(vertices, [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], material)
+    }
+
+    pub fn with_uvs(vertices: [Vec3; 3], uvs: [(Float, Float); 3], material: Material) -> Triangle {
         Triangle {
             vertices,
+            uvs,
             material,
         }
     }
+
+    /// Derivatives of the position along u and v, from solving for the linear
+    /// map taking UV differences to edges. Degenerate UVs fall back to any
+    /// frame around the normal.
+    fn uv_derivatives(&self, normal: Vec3) -> (Vec3, Vec3) {
+        let [p0, p1, p2] = self.vertices;
+        let [uv0, uv1, uv2] = self.uvs;
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let (dp02, dp12) = (p0 - p2, p1 - p2);
+        let determinant = du02 * dv12 - dv02 * du12;
+        if determinant.abs() < 1e-12 {
+            let axes = Mat3::onb(normal);
+            return (axes.col(0), axes.col(1));
+        }
+        let inv = 1.0 / determinant;
+        ((dp02 * dv12 - dp12 * dv02) * inv, (dp12 * du02 - dp02 * du12) * inv)
+    }
 }
 
 impl Hittable for Triangle {
-    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
-        let e1 = self.vertices[1] - self.vertices[0];
-        let e2 = self.vertices[2] - self.vertices[0];
-        let h = ray.direction.cross(e2);
-        let a = e1.dot(h);
-
-        if a > -1e-6 && a < 1e-6 {
+    /// Möller-Trumbore intersection.
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let [p0, p1, p2] = self.vertices;
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+        let h = Vec3::cross(&ray.direction(), &e2);
+        let a = Vec3::dot(&e1, &h);
+        if a.abs() <= Float::EPSILON * e1.length() * h.length() {
             return None;
         }
 
         let f = 1.0 / a;
-        let s = ray.origin - self.vertices[0];
-        let u = f * s.dot(h);
-
-        if u < 0.0 || u > 1.0 {
+        let s = ray.origin() - p0;
+        let b1 = f * Vec3::dot(&s, &h);
+        if !(0.0..=1.0).contains(&b1) {
             return None;
         }
-
-        let q = s.cross(e1);
-        let v = f * ray.direction.dot(q);
-
-        if v < 0.0 || u + v > 1.0 {
+        let q = Vec3::cross(&s, &e1);
+        let b2 = f * Vec3::dot(&ray.direction(), &q);
+        if b2 < 0.0 || b1 + b2 > 1.0 {
             return None;
         }
-
-        let t = f * e2.dot(q);
-        if t > 1e-6 {
-            let hit_point = ray.point_at_parameter(t);
-            let normal = self.get_normal();
-            let material = self.material.clone();
-            return Some(HitRecord { t, hit_point, normal, material });
+        let t = f * Vec3::dot(&e2, &q);
+        if t <= t_min || t >= t_max {
+            return None;
         }
 
-        None
+        // Rebuild the point from the barycentrics, which bounds its error
+        // independently of `t`
+        let b0 = 1.0 - b1 - b2;
+        let p = p0 * b0 + p1 * b1 + p2 * b2;
+        let p_error = ((p0 * b0).abs() + (p1 * b1).abs() + (p2 * b2).abs()) * gamma(7);
+
+        let [uv0, uv1, uv2] = self.uvs;
+        let normal = Vec3::unit_vector(&Vec3::cross(&e1, &e2));
+        let (dpdu, dpdv) = self.uv_derivatives(normal);
+        let mut rec = HitRecord {
+            t,
+            p,
+            p_error,
+            u: b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            v: b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+            dpdu,
+            dpdv,
+            material: self.material.clone(),
+            ..Default::default()
+        };
+        rec.set_face_normal(ray, normal);
+        Some(rec)
     }
 
-    fn get_normal(&self) -> Vector3 {
-        (self.vertices[1] - self.vertices[0]).cross(self.vertices[2] - self.vertices[0]).normalize()
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.vertices, FLAT_BOX_DELTA))
     }
 }
-
-
-
-
@@ -1,15 +1,18 @@
 use std::ops;
 use rand::Rng;
+use crate::float::Float;
+#[cfg(feature = "simd")]
+use crate::float::FloatX4;
 
 
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct Vec3 {
-    pub e: [f64; 3]
+    pub e: [Float; 3]
 }
 
 impl Vec3 {
-    pub fn new(e0: f64, e1: f64, e2: f64) -> Vec3 {
+    pub fn new(e0: Float, e1: Float, e2: Float) -> Vec3 {
         Vec3 {
             e: [e0, e1, e2]
         }  
@@ -18,11 +21,11 @@ impl Vec3 {
     pub fn random() -> Vec3 {
         let mut rng = rand::thread_rng();
         Vec3 {
-            e: [rng.gen::<f64>()*rng.gen::<f64>(), rng.gen::<f64>()*rng.gen::<f64>(), rng.gen::<f64>()*rng.gen::<f64>()]
+            e: [rng.gen::<Float>()*rng.gen::<Float>(), rng.gen::<Float>()*rng.gen::<Float>(), rng.gen::<Float>()*rng.gen::<Float>()]
         }
     }
 
-    pub fn length(self) -> f64 {
+    pub fn length(self) -> Float {
         (self.e[0]*self.e[0] + self.e[1]*self.e[1] + self.e[2]*self.e[2]).sqrt()
     }
 
@@ -30,24 +33,32 @@ impl Vec3 {
        *v / v.length()
     }
 
-    pub fn x(self) -> f64 {
+    pub fn x(self) -> Float {
         self.e[0]
     }
 
-    pub fn y(self) -> f64 {
+    pub fn y(self) -> Float {
         self.e[1]
     }
 
-    pub fn z(self) -> f64 {
+    pub fn z(self) -> Float {
         self.e[2]
     }
 
-    pub fn dot(a: &Vec3, b: &Vec3) -> f64 {
-        a.e[0] * b.e[0] + a.e[1] * b.e[1] + a.e[2] * b.e[2]
+    pub fn dot(a: &Vec3, b: &Vec3) -> Float {
+        #[cfg(feature = "simd")]
+        { (a.lanes() * b.lanes()).reduce_add() }
+        #[cfg(not(feature = "simd"))]
+        { a.e[0] * b.e[0] + a.e[1] * b.e[1] + a.e[2] * b.e[2] }
     }
 
-    pub fn squared_length(self) -> f64 {
-        self.e[0] * self.e[0] + self.e[1] * self.e[1] + self.e[2] * self.e[2]
+    pub fn squared_length(self) -> Float {
+        Vec3::dot(&self, &self)
+    }
+
+    /// Relative luminance of a linear RGB colour, with Rec. 709 primaries.
+    pub fn luminance(self) -> Float {
+        0.2126 * self.x() + 0.7152 * self.y() + 0.0722 * self.z()
     }
 
     pub fn cross(a: &Vec3, b: &Vec3) -> Vec3 {
@@ -57,15 +68,32 @@ impl Vec3 {
             a.x() * b.y() - a.y() * b.x()]
         }
     }
-    
+
+    /// Componentwise absolute value.
+    pub fn abs(self) -> Vec3 {
+        Vec3::new(self.e[0].abs(), self.e[1].abs(), self.e[2].abs())
+    }
 
     /// Converts floats to ints, colors are
     /// supposed to be integer values between 0 and 255.
-    pub fn colorize(&mut self) -> () {
+    pub fn colorize(&mut self) {
         self.e[0] = self.e[0].floor();
         self.e[1] = self.e[1].floor();
         self.e[2] = self.e[2].floor();
     }
+
+    /// The components in the first three lanes of a SIMD register, the
+    /// fourth lane is zero so it does not disturb horizontal sums.
+    #[cfg(feature = "simd")]
+    fn lanes(self) -> FloatX4 {
+        FloatX4::new([self.e[0], self.e[1], self.e[2], 0.0])
+    }
+
+    #[cfg(feature = "simd")]
+    fn from_lanes(v: FloatX4) -> Vec3 {
+        let a = v.to_array();
+        Vec3::new(a[0], a[1], a[2])
+    }
 }
 
 
@@ -83,6 +111,9 @@ impl ops::Add for Vec3 {
     type Output = Self;
 
     fn add(self, rhs: Vec3) -> Self::Output {
+        #[cfg(feature = "simd")]
+        { Vec3::from_lanes(self.lanes() + rhs.lanes()) }
+        #[cfg(not(feature = "simd"))]
         Vec3 {
             e: [
                 self.e[0] + rhs.e[0],
@@ -97,6 +128,9 @@ impl ops::Sub for Vec3 {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
+        #[cfg(feature = "simd")]
+        { Vec3::from_lanes(self.lanes() - rhs.lanes()) }
+        #[cfg(not(feature = "simd"))]
         Vec3 {
             e: [
                 self.e[0] - rhs.e[0],
@@ -107,7 +141,7 @@ impl ops::Sub for Vec3 {
     }
 }
 
-impl ops::Mul<Vec3> for f64 {
+impl ops::Mul<Vec3> for Float {
     type Output = Vec3;
 
     fn mul(self, rhs: Vec3) -> Self::Output {
@@ -117,10 +151,13 @@ impl ops::Mul<Vec3> for f64 {
     }
 }
 
-impl ops::Mul<f64> for Vec3 {
+impl ops::Mul<Float> for Vec3 {
     type Output = Self;
 
-    fn mul(self, rhs: f64) -> Self::Output {
+    fn mul(self, rhs: Float) -> Self::Output {
+        #[cfg(feature = "simd")]
+        { Vec3::from_lanes(self.lanes() * FloatX4::splat(rhs)) }
+        #[cfg(not(feature = "simd"))]
         Vec3 {
             e: [self.e[0] * rhs, self.e[1] * rhs, self.e[2] * rhs],
         }
@@ -131,6 +168,9 @@ impl ops::Mul for Vec3 {
     type Output = Self;
 
     fn mul(self, rhs: Self) -> Self::Output {
+        #[cfg(feature = "simd")]
+        { Vec3::from_lanes(self.lanes() * rhs.lanes()) }
+        #[cfg(not(feature = "simd"))]
         Vec3 {
             e: [
                 self.e[0] * rhs.e[0],
@@ -141,10 +181,10 @@ impl ops::Mul for Vec3 {
     }
 }
 
-impl ops::Div<f64> for Vec3 {
+impl ops::Div<Float> for Vec3 {
     type Output = Self;
 
-    fn div(self, rhs: f64) -> Self::Output {
+    fn div(self, rhs: Float) -> Self::Output {
         let k = 1.0 / rhs;
 
         Vec3 {
@@ -158,4 +198,194 @@ impl ops::AddAssign for Vec3 {
     fn add_assign(&mut self, rhs: Vec3) {
         self.e = [self.e[0] + rhs.e[0], self.e[1] + rhs.e[1], self.e[2] + rhs.e[2]]
     }
-}
\ No newline at end of file
+}
+
+/// A position in space. Points can be moved by vectors and subtracted from
+/// each other, but not added together.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Point3(pub Vec3);
+
+/// A surface normal, always of unit length.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Normal3(Vec3);
+
+/// Linear RGB radiance or reflectance.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Color(pub Vec3);
+
+impl Point3 {
+    pub fn new(x: Float, y: Float, z: Float) -> Point3 {
+        Point3(Vec3::new(x, y, z))
+    }
+}
+
+impl Normal3 {
+    /// Normalizes `v` into a normal.
+    pub fn new(v: Vec3) -> Normal3 {
+        Normal3(Vec3::unit_vector(&v))
+    }
+
+    /// Flips the normal to lie in the same hemisphere as `v`.
+    pub fn face_forward(self, v: Vec3) -> Normal3 {
+        if Vec3::dot(&self.0, &v) < 0.0 { Normal3(-self.0) } else { self }
+    }
+}
+
+impl Color {
+    pub fn new(r: Float, g: Float, b: Float) -> Color {
+        Color(Vec3::new(r, g, b))
+    }
+
+    /// Relative luminance with Rec. 709 primaries.
+    pub fn luminance(self) -> Float {
+        self.0.luminance()
+    }
+}
+
+impl From<Vec3> for Point3 {
+    fn from(v: Vec3) -> Self {
+        Point3(v)
+    }
+}
+
+impl From<Vec3> for Normal3 {
+    fn from(v: Vec3) -> Self {
+        Normal3::new(v)
+    }
+}
+
+impl From<Vec3> for Color {
+    fn from(v: Vec3) -> Self {
+        Color(v)
+    }
+}
+
+impl From<Point3> for Vec3 {
+    fn from(p: Point3) -> Self {
+        p.0
+    }
+}
+
+impl From<Normal3> for Vec3 {
+    fn from(n: Normal3) -> Self {
+        n.0
+    }
+}
+
+impl From<Color> for Vec3 {
+    fn from(c: Color) -> Self {
+        c.0
+    }
+}
+
+impl ops::Sub for Point3 {
+    type Output = Vec3;
+
+    fn sub(self, rhs: Point3) -> Self::Output {
+        self.0 - rhs.0
+    }
+}
+
+impl ops::Add<Vec3> for Point3 {
+    type Output = Point3;
+
+    fn add(self, rhs: Vec3) -> Self::Output {
+        Point3(self.0 + rhs)
+    }
+}
+
+impl ops::Sub<Vec3> for Point3 {
+    type Output = Point3;
+
+    fn sub(self, rhs: Vec3) -> Self::Output {
+        Point3(self.0 - rhs)
+    }
+}
+
+impl ops::Neg for Normal3 {
+    type Output = Normal3;
+
+    fn neg(self) -> Self::Output {
+        Normal3(-self.0)
+    }
+}
+
+impl ops::Add for Color {
+    type Output = Color;
+
+    fn add(self, rhs: Color) -> Self::Output {
+        Color(self.0 + rhs.0)
+    }
+}
+
+impl ops::AddAssign for Color {
+    fn add_assign(&mut self, rhs: Color) {
+        self.0 += rhs.0
+    }
+}
+
+impl ops::Mul for Color {
+    type Output = Color;
+
+    fn mul(self, rhs: Color) -> Self::Output {
+        Color(self.0 * rhs.0)
+    }
+}
+
+impl ops::Mul<Float> for Color {
+    type Output = Color;
+
+    fn mul(self, rhs: Float) -> Self::Output {
+        Color(self.0 * rhs)
+    }
+}
+
+impl ops::Div<Float> for Color {
+    type Output = Color;
+
+    fn div(self, rhs: Float) -> Self::Output {
+        Color(self.0 / rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_move_by_vectors() {
+        let (a, b) = (Point3::new(1.0, 2.0, 3.0), Point3::new(4.0, 6.0, 3.0));
+        let d = b - a;
+        assert_eq!(d, Vec3::new(3.0, 4.0, 0.0));
+        assert_eq!(a + d, b);
+        assert_eq!(b - d, a);
+        assert_eq!(Vec3::from(Point3::from(d)), d);
+    }
+
+    #[test]
+    fn normals_are_unit_length() {
+        let n = Normal3::new(Vec3::new(0.0, 3.0, 4.0));
+        assert!((Vec3::from(n).length() - 1.0).abs() < 1e-6);
+        assert!((Vec3::from(n) - Vec3::new(0.0, 0.6, 0.8)).length() < 1e-6);
+        assert_eq!(Normal3::from(Vec3::new(0.0, 0.0, -2.0)), -Normal3::new(Vec3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn face_forward_flips_into_the_hemisphere_of_v() {
+        let n = Normal3::new(Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(n.face_forward(Vec3::new(1.0, 0.5, 0.0)), n);
+        assert_eq!(n.face_forward(Vec3::new(1.0, -0.5, 0.0)), -n);
+    }
+
+    #[test]
+    fn colors_mix_componentwise() {
+        let (a, b) = (Color::new(0.5, 1.0, 0.25), Color::new(2.0, 0.5, 4.0));
+        assert_eq!(a * b, Color::new(1.0, 0.5, 1.0));
+        assert_eq!((a + b) * 2.0 / 4.0, Color::new(1.25, 0.75, 2.125));
+        let mut sum = Color::default();
+        sum += a;
+        assert_eq!(Vec3::from(sum), Vec3::new(0.5, 1.0, 0.25));
+        assert!((Color::new(1.0, 1.0, 1.0).luminance() - 1.0).abs() < 1e-6);
+        assert_eq!(Color::from(Vec3::new(0.0, 1.0, 0.0)).luminance(), 0.7152);
+    }
+}